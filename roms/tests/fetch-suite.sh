@@ -0,0 +1,11 @@
+#!/bin/sh
+# Downloads the ROMs of Timendus' CHIP-8 test suite into suite/ next to this
+# script, for the cases in suite.txt.
+set -eu
+VERSION=v4.1
+BASE="https://raw.githubusercontent.com/Timendus/chip8-test-suite/$VERSION/bin"
+DIR="$(dirname "$0")/suite"
+mkdir -p "$DIR"
+for ROM in 1-chip8-logo 2-ibm-logo 3-corax+ 4-flags 5-quirks 6-keypad 7-beep; do
+    curl -fsSL -o "$DIR/$ROM.ch8" "$BASE/$(echo "$ROM" | sed 's/+/%2B/').ch8"
+done
@@ -1,9 +1,125 @@
-#[derive(Debug)]
+pub const DEFAULT_STACK_DEPTH: usize = 16;
+pub const QUIRK_PROFILES: [&str; 4] = ["modern", "vip", "schip", "xochip"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StackPolicy {
+    Halt,
+    Wrap,
+    Debug,
+}
+
+impl StackPolicy {
+    pub fn from_name(name: &str) -> Option<StackPolicy> {
+        match name.to_lowercase().as_str() {
+            "halt" => Some(StackPolicy::Halt),
+            "wrap" => Some(StackPolicy::Wrap),
+            "debug" => Some(StackPolicy::Debug),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StackPolicy::Halt => "halt",
+            StackPolicy::Wrap => "wrap",
+            StackPolicy::Debug => "debug",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpcodePolicy {
+    Ignore,
+    Warn,
+    Halt,
+    Debug,
+}
+
+impl OpcodePolicy {
+    pub fn from_name(name: &str) -> Option<OpcodePolicy> {
+        match name.to_lowercase().as_str() {
+            "ignore" => Some(OpcodePolicy::Ignore),
+            "warn" => Some(OpcodePolicy::Warn),
+            "halt" => Some(OpcodePolicy::Halt),
+            "debug" => Some(OpcodePolicy::Debug),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpcodePolicy::Ignore => "ignore",
+            OpcodePolicy::Warn => "warn",
+            OpcodePolicy::Halt => "halt",
+            OpcodePolicy::Debug => "debug",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quirk {
+    ModernShift,
+    ModernJumpOffset,
+    ModernStoreAndLoad,
+    ModernIndexAddition,
+    WrapSprites,
+    DisplayWait,
+    VfReset,
+}
+
+pub const QUIRKS: [Quirk; 7] = [
+    Quirk::ModernShift,
+    Quirk::ModernJumpOffset,
+    Quirk::ModernStoreAndLoad,
+    Quirk::ModernIndexAddition,
+    Quirk::WrapSprites,
+    Quirk::DisplayWait,
+    Quirk::VfReset,
+];
+
+impl Quirk {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Quirk::ModernShift => "Modern shift",
+            Quirk::ModernJumpOffset => "Modern jump offset",
+            Quirk::ModernStoreAndLoad => "Modern store/load",
+            Quirk::ModernIndexAddition => "Modern index addition",
+            Quirk::WrapSprites => "Wrap sprites",
+            Quirk::DisplayWait => "Display wait",
+            Quirk::VfReset => "VF reset",
+        }
+    }
+
+    // Used in movie files, named like the quirk flags where there is one.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Quirk::ModernShift => "modern-shift",
+            Quirk::ModernJumpOffset => "modern-jump-offset",
+            Quirk::ModernStoreAndLoad => "modern-store-and-load",
+            Quirk::ModernIndexAddition => "modern-index-addition",
+            Quirk::WrapSprites => "wrap-sprites",
+            Quirk::DisplayWait => "display-wait",
+            Quirk::VfReset => "vf-reset",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Quirk> {
+        QUIRKS.iter().copied().find(|quirk| quirk.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct CpuConfig {
     modern_shift: bool,
     modern_jump_offset: bool,
     modern_store_and_load: bool,
     modern_index_addition: bool,
+    stack_depth: usize,
+    stack_policy: StackPolicy,
+    opcode_policy: OpcodePolicy,
+    wrap_sprites: bool,
+    display_wait: bool,
+    vf_reset: bool,
 }
 
 impl CpuConfig {
@@ -18,6 +134,12 @@ impl CpuConfig {
             modern_jump_offset,
             modern_store_and_load,
             modern_index_addition,
+            stack_depth: DEFAULT_STACK_DEPTH,
+            stack_policy: StackPolicy::Halt,
+            opcode_policy: OpcodePolicy::Ignore,
+            wrap_sprites: false,
+            display_wait: false,
+            vf_reset: false,
         }
     }
 
@@ -27,9 +149,60 @@ impl CpuConfig {
             modern_jump_offset: false,
             modern_store_and_load: true,
             modern_index_addition: true,
+            stack_depth: DEFAULT_STACK_DEPTH,
+            stack_policy: StackPolicy::Halt,
+            opcode_policy: OpcodePolicy::Ignore,
+            wrap_sprites: false,
+            display_wait: false,
+            vf_reset: false,
         }
     }
 
+    // The quirk settings of a known platform, named as in QUIRK_PROFILES.
+    pub fn profile(name: &str) -> Option<CpuConfig> {
+        match name.to_lowercase().as_str() {
+            "modern" => Some(CpuConfig::default()),
+            "vip" => {
+                let mut cpu_config: CpuConfig = CpuConfig::new(false, false, false, false);
+                cpu_config.set_display_wait(true);
+                cpu_config.set_vf_reset(true);
+                Some(cpu_config)
+            }
+            "schip" => Some(CpuConfig::new(true, true, true, false)),
+            "xochip" => {
+                let mut cpu_config: CpuConfig = CpuConfig::new(false, false, false, false);
+                cpu_config.set_wrap_sprites(true);
+                Some(cpu_config)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn quirk(&self, quirk: Quirk) -> bool {
+        match quirk {
+            Quirk::ModernShift => self.modern_shift,
+            Quirk::ModernJumpOffset => self.modern_jump_offset,
+            Quirk::ModernStoreAndLoad => self.modern_store_and_load,
+            Quirk::ModernIndexAddition => self.modern_index_addition,
+            Quirk::WrapSprites => self.wrap_sprites,
+            Quirk::DisplayWait => self.display_wait,
+            Quirk::VfReset => self.vf_reset,
+        }
+    }
+
+    pub fn toggle_quirk(&mut self, quirk: Quirk) {
+        let flag: &mut bool = match quirk {
+            Quirk::ModernShift => &mut self.modern_shift,
+            Quirk::ModernJumpOffset => &mut self.modern_jump_offset,
+            Quirk::ModernStoreAndLoad => &mut self.modern_store_and_load,
+            Quirk::ModernIndexAddition => &mut self.modern_index_addition,
+            Quirk::WrapSprites => &mut self.wrap_sprites,
+            Quirk::DisplayWait => &mut self.display_wait,
+            Quirk::VfReset => &mut self.vf_reset,
+        };
+        *flag = !*flag;
+    }
+
     pub fn modern_shift(&self) -> bool {
         self.modern_shift
     }
@@ -45,4 +218,52 @@ impl CpuConfig {
     pub fn modern_index_addition(&self) -> bool {
         self.modern_index_addition
     }
+
+    pub fn stack_depth(&self) -> usize {
+        self.stack_depth
+    }
+
+    pub fn set_stack_depth(&mut self, stack_depth: usize) {
+        self.stack_depth = stack_depth;
+    }
+
+    pub fn stack_policy(&self) -> StackPolicy {
+        self.stack_policy
+    }
+
+    pub fn set_stack_policy(&mut self, stack_policy: StackPolicy) {
+        self.stack_policy = stack_policy;
+    }
+
+    pub fn opcode_policy(&self) -> OpcodePolicy {
+        self.opcode_policy
+    }
+
+    pub fn set_opcode_policy(&mut self, opcode_policy: OpcodePolicy) {
+        self.opcode_policy = opcode_policy;
+    }
+
+    pub fn wrap_sprites(&self) -> bool {
+        self.wrap_sprites
+    }
+
+    pub fn set_wrap_sprites(&mut self, wrap_sprites: bool) {
+        self.wrap_sprites = wrap_sprites;
+    }
+
+    pub fn display_wait(&self) -> bool {
+        self.display_wait
+    }
+
+    pub fn set_display_wait(&mut self, display_wait: bool) {
+        self.display_wait = display_wait;
+    }
+
+    pub fn vf_reset(&self) -> bool {
+        self.vf_reset
+    }
+
+    pub fn set_vf_reset(&mut self, vf_reset: bool) {
+        self.vf_reset = vf_reset;
+    }
 }
@@ -0,0 +1,67 @@
+use std::{error::Error, fmt, io};
+
+use crate::{guestsystem::components::memory::StackFault, logic::octo::compiler::OctoError};
+
+#[derive(Debug)]
+pub enum EmulatorError {
+    MissingRomPath,
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    Compile {
+        path: String,
+        source: OctoError,
+    },
+    RomTooLarge {
+        size: usize,
+        max: usize,
+    },
+    MemoryFault {
+        address: usize,
+    },
+    StackFault {
+        pc: u16,
+        fault: StackFault,
+        depth: usize,
+    },
+    InvalidOpcode {
+        pc: u16,
+        opcode: u16,
+    },
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmulatorError::MissingRomPath => write!(f, "No path provided for ROM."),
+            EmulatorError::Io { path, source } => write!(f, "Cannot read {}: {}", path, source),
+            EmulatorError::Compile { path, source } => write!(f, "{}: {}", path, source),
+            EmulatorError::RomTooLarge { size, max } => write!(
+                f,
+                "The ROM is {} bytes long, but at most {} bytes fit in memory.",
+                size, max
+            ),
+            EmulatorError::MemoryFault { address } => {
+                write!(f, "Memory fault at address 0x{:04X}.", address)
+            }
+            EmulatorError::StackFault { pc, fault, depth } => write!(
+                f,
+                "{} at PC 0x{:04X} (stack depth is {}).",
+                fault, pc, depth
+            ),
+            EmulatorError::InvalidOpcode { pc, opcode } => {
+                write!(f, "Invalid opcode {:04X} at PC 0x{:04X}.", opcode, pc)
+            }
+        }
+    }
+}
+
+impl Error for EmulatorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EmulatorError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -1,27 +1,27 @@
-use std::time::{Duration, Instant};
-
-use rand::Rng;
-use sdl2::{event::Event, keyboard::Scancode, EventPump};
+use std::{collections::BTreeMap, mem};
 
 use crate::{
-    config::CpuConfig,
-    logic::interpreter::{self, Interpreter},
+    config::{CpuConfig, OpcodePolicy, Quirk, StackPolicy},
+    error::EmulatorError,
+    logic::{interpreter::Interpreter, tracer::Tracer},
 };
 
 use super::{
     display::DisplayScreen,
     keypad::Keypad,
-    memory::{Memory, PROGRAM_ADDRESS},
+    memory::{Memory, StackFault, PROGRAM_ADDRESS},
+    random::{RandomKind, RandomSource},
 };
 
-const VARIABLE_REGISTER_COUNT: usize = 16;
+pub const VARIABLE_REGISTER_COUNT: usize = 16;
 const MAX_INDEX_REG_VAL: u16 = 0x0FFF;
-const TIMER_HZ: f64 = 60.0;
 
 #[derive(Debug)]
 pub enum CpuInst {
     ExecMlrNNN(u16),
     Cls,
+    Lores,
+    Hires,
     JmpNNN(u16),
     SubRoutineNNN(u16),
     SubRoutineReturn,
@@ -65,7 +65,18 @@ pub struct Cpu {
     delay_timer: u8,
     sound_timer: u8,
     config: CpuConfig,
-    last_time: Instant,
+    instruction_address: u16,
+    instruction_raw: u16,
+    tracer: Option<Tracer>,
+    break_reason: Option<String>,
+    waiting_for_vblank: bool,
+    random: RandomSource,
+    unknown_opcodes: BTreeMap<u16, UnknownOpcode>,
+}
+
+struct UnknownOpcode {
+    count: u64,
+    first_pc: u16,
 }
 
 impl Cpu {
@@ -77,10 +88,79 @@ impl Cpu {
             delay_timer: 0,
             sound_timer: 0,
             config,
-            last_time: Instant::now(),
+            instruction_address: 0,
+            instruction_raw: 0,
+            tracer: None,
+            break_reason: None,
+            waiting_for_vblank: false,
+            random: RandomSource::new(RandomKind::Std, rand::random()),
+            unknown_opcodes: BTreeMap::new(),
         }
     }
 
+    pub fn take_break_reason(&mut self) -> Option<String> {
+        self.break_reason.take()
+    }
+
+    // True once after a draw when the display wait quirk is on, the rest of
+    // the frame is then skipped like on the COSMAC VIP.
+    pub fn take_vblank_wait(&mut self) -> bool {
+        mem::take(&mut self.waiting_for_vblank)
+    }
+
+    pub fn get_waiting_for_vblank(&self) -> bool {
+        self.waiting_for_vblank
+    }
+
+    pub fn get_random(&self) -> &RandomSource {
+        &self.random
+    }
+
+    pub fn set_random(&mut self, random: RandomSource) {
+        self.random = random;
+    }
+
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    pub fn get_config(&self) -> &CpuConfig {
+        &self.config
+    }
+
+    pub fn toggle_quirk(&mut self, quirk: Quirk) {
+        self.config.toggle_quirk(quirk);
+    }
+
+    // Puts the registers and timers back to power-on values, keeping the
+    // configuration, the tracer and the random number source.
+    pub fn reset(&mut self) {
+        self.program_counter = PROGRAM_ADDRESS as u16;
+        self.index_register = 0;
+        self.variable_registers = [0; VARIABLE_REGISTER_COUNT];
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.waiting_for_vblank = false;
+        self.break_reason = None;
+    }
+
+    pub fn restore(
+        &mut self,
+        pc: u16,
+        index: u16,
+        registers: [u8; VARIABLE_REGISTER_COUNT],
+        delay_timer: u8,
+        sound_timer: u8,
+        waiting_for_vblank: bool,
+    ) {
+        self.program_counter = pc;
+        self.index_register = index;
+        self.variable_registers = registers;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.waiting_for_vblank = waiting_for_vblank;
+    }
+
     pub fn point_pc_to_program(&mut self) {
         self.program_counter = PROGRAM_ADDRESS as u16;
     }
@@ -93,6 +173,22 @@ impl Cpu {
         self.program_counter
     }
 
+    pub fn get_index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    pub fn get_variable_registers(&self) -> &[u8; VARIABLE_REGISTER_COUNT] {
+        &self.variable_registers
+    }
+
+    pub fn get_delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn get_sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
     pub fn set_pc(&mut self, address: u16, max_address: u16) {
         if address > max_address {
             self.program_counter = PROGRAM_ADDRESS as u16;
@@ -101,10 +197,16 @@ impl Cpu {
         self.program_counter = address
     }
 
-    pub fn fetch(&mut self, memory: &Memory, interpreter: &Interpreter) -> u16 {
-        let instruction: u16 = interpreter.fetch(memory, self.program_counter);
+    pub fn fetch(
+        &mut self,
+        memory: &Memory,
+        interpreter: &Interpreter,
+    ) -> Result<u16, EmulatorError> {
+        let instruction: u16 = interpreter.fetch(memory, self.program_counter)?;
+        self.instruction_address = self.program_counter;
+        self.instruction_raw = instruction;
         self.program_counter = interpreter.next_pc(self.program_counter);
-        instruction
+        Ok(instruction)
     }
 
     pub fn decode(&self, raw_instruction: u16, interpreter: &Interpreter) -> CpuInst {
@@ -118,16 +220,26 @@ impl Cpu {
         memory: &mut Memory,
         display: &mut DisplayScreen,
         keypad: &Keypad,
-        event_pump: &mut EventPump,
-    ) {
+    ) -> Result<(), EmulatorError> {
+        if let Some(mut tracer) = self.tracer.take() {
+            let mnemonic: String = interpreter.mnemonic(instruction);
+            tracer.trace(
+                self.instruction_address,
+                self.instruction_raw,
+                &mnemonic,
+                self,
+                memory,
+            );
+            self.tracer = Some(tracer);
+        }
         match instruction {
-            CpuInst::ExecMlrNNN(_) => {}
+            CpuInst::ExecMlrNNN(_) => self.handle_unknown_opcode()?,
             CpuInst::Cls => display.clear_screen(),
+            CpuInst::Lores => display.set_hires(false),
+            CpuInst::Hires => display.set_hires(true),
             CpuInst::JmpNNN(nnn) => self.program_counter = *nnn,
-            CpuInst::SubRoutineNNN(nnn) => self.enter_subroutine(*nnn, memory),
-            CpuInst::SubRoutineReturn => {
-                self.program_counter = memory.pop_stack().expect("Error: Cannot pop from stack")
-            }
+            CpuInst::SubRoutineNNN(nnn) => self.enter_subroutine(*nnn, memory)?,
+            CpuInst::SubRoutineReturn => self.return_from_subroutine(memory)?,
             CpuInst::SkipIfEqXNN(x, nn) => {
                 self.skip_instruction(*x as usize, *nn, true, interpreter)
             }
@@ -152,18 +264,9 @@ impl Cpu {
             CpuInst::SetXY(x, y) => {
                 self.variable_registers[*x as usize] = self.variable_registers[*y as usize]
             }
-            CpuInst::BitOrXY(x, y) => {
-                self.variable_registers[*x as usize] =
-                    self.variable_registers[*x as usize] | self.variable_registers[*y as usize]
-            }
-            CpuInst::BitAndXY(x, y) => {
-                self.variable_registers[*x as usize] =
-                    self.variable_registers[*x as usize] & self.variable_registers[*y as usize]
-            }
-            CpuInst::BitXorXY(x, y) => {
-                self.variable_registers[*x as usize] =
-                    self.variable_registers[*x as usize] ^ self.variable_registers[*y as usize]
-            }
+            CpuInst::BitOrXY(x, y) => self.bit_op(*x as usize, *y as usize, |vx, vy| vx | vy),
+            CpuInst::BitAndXY(x, y) => self.bit_op(*x as usize, *y as usize, |vx, vy| vx & vy),
+            CpuInst::BitXorXY(x, y) => self.bit_op(*x as usize, *y as usize, |vx, vy| vx ^ vy),
             CpuInst::AddXY(x, y) => self.add_regs(*x as usize, *y as usize),
             CpuInst::SubtFromLeftXY(x, y) => self.subt_regs_left(*x as usize, *y as usize),
             CpuInst::SubtFromRightXY(x, y) => self.subt_regs_right(*x as usize, *y as usize),
@@ -172,15 +275,28 @@ impl Cpu {
             CpuInst::SetIndexNNN(nnn) => self.index_register = *nnn,
             CpuInst::JmpOffsetNNN(nnn) => self.jump_with_offset(*nnn, interpreter),
             CpuInst::RandomXNN(x, nn) => {
-                let random_number: u8 = rand::thread_rng().gen();
+                let random_number: u8 = self.random.next(memory);
                 self.variable_registers[*x as usize] = random_number & *nn;
             }
-            CpuInst::DisplayXYN(x, y, n) => display.display(
-                self.variable_registers[*x as usize],
-                self.variable_registers[*y as usize],
-                memory.get_heap_slice(self.index_register, *n as u16),
-                self,
-            ),
+            CpuInst::DisplayXYN(x, y, n) => {
+                // DXY0 draws a 16x16 sprite of 32 bytes in hires.
+                let (sprite_width, length): (usize, u16) = if *n == 0 && display.is_hires() {
+                    (16, 32)
+                } else {
+                    (8, *n as u16)
+                };
+                let sprite: &[u8] = memory.get_heap_slice(self.index_register, length)?;
+                let wrap: bool = self.config.wrap_sprites();
+                display.display(
+                    self.variable_registers[*x as usize],
+                    self.variable_registers[*y as usize],
+                    sprite,
+                    sprite_width,
+                    wrap,
+                    self,
+                );
+                self.waiting_for_vblank = self.config.display_wait();
+            }
             CpuInst::SkipIfKeyX(x) => {
                 self.skip_if_key(*x as usize, interpreter, keypad, true);
             }
@@ -193,11 +309,14 @@ impl Cpu {
             CpuInst::AddToIndexX(x) => self.add_to_index(*x as usize),
             CpuInst::WaitForKeyX(x) => self.wait_for_key(*x as usize, keypad, interpreter),
             CpuInst::SetIndexToFontX(x) => self.set_index_to_font(*x as usize, memory),
-            CpuInst::DecimalConversionX(x) => self.store_three_decimal_digits(*x as usize, memory),
-            CpuInst::StoreInMemoryX(x) => self.store_x_regs(*x as usize + 1, memory),
-            CpuInst::LoadFromMemoryX(x) => self.load_x_regs(*x as usize + 1, memory),
-            CpuInst::InvalidInstruction => {}
+            CpuInst::DecimalConversionX(x) => {
+                self.store_three_decimal_digits(*x as usize, memory)?
+            }
+            CpuInst::StoreInMemoryX(x) => self.store_x_regs(*x as usize + 1, memory)?,
+            CpuInst::LoadFromMemoryX(x) => self.load_x_regs(*x as usize + 1, memory)?,
+            CpuInst::InvalidInstruction => self.handle_unknown_opcode()?,
         }
+        Ok(())
     }
 
     fn jump_with_offset(&mut self, nnn: u16, interpreter: &Interpreter) {
@@ -211,67 +330,53 @@ impl Cpu {
         }
     }
 
-    fn shift_left(&mut self, x: usize, y: usize) {
-        if !self.config.modern_shift() {
-            self.variable_registers[x as usize] = self.variable_registers[y as usize];
-        }
-        let msb: bool = self.variable_registers[x as usize] & 0x80 == 0x80;
-        self.variable_registers[x as usize] = self.variable_registers[x as usize] << 1;
-        if msb {
-            self.set_flag_register(1);
-        } else {
+    // VX is written before VF, so the flag wins when X is F.
+    fn set_with_flag(&mut self, x: usize, result: u8, flag: bool) {
+        self.variable_registers[x] = result;
+        self.set_flag_register(flag as u8);
+    }
+
+    fn bit_op(&mut self, x: usize, y: usize, op: fn(u8, u8) -> u8) {
+        self.variable_registers[x] = op(self.variable_registers[x], self.variable_registers[y]);
+        if self.config.vf_reset() {
             self.set_flag_register(0);
         }
     }
 
+    fn shift_left(&mut self, x: usize, y: usize) {
+        let value: u8 = if self.config.modern_shift() {
+            self.variable_registers[x]
+        } else {
+            self.variable_registers[y]
+        };
+        self.set_with_flag(x, value << 1, value & 0x80 == 0x80);
+    }
+
     fn shift_right(&mut self, x: usize, y: usize) {
-        if !self.config.modern_shift() {
-            self.variable_registers[x as usize] = self.variable_registers[y as usize];
-        }
-        let lsb: bool = self.variable_registers[x as usize] & 1 == 1;
-        self.variable_registers[x as usize] = self.variable_registers[x as usize] >> 1;
-        if lsb {
-            self.set_flag_register(1);
+        let value: u8 = if self.config.modern_shift() {
+            self.variable_registers[x]
         } else {
-            self.set_flag_register(0);
-        }
+            self.variable_registers[y]
+        };
+        self.set_with_flag(x, value >> 1, value & 1 == 1);
     }
 
     fn add_regs(&mut self, x: usize, y: usize) {
-        let overflow: bool = self.variable_registers[x as usize]
-            .checked_add(self.variable_registers[y as usize])
-            .is_none();
-        self.variable_registers[x as usize] =
-            self.variable_registers[x as usize].wrapping_add(self.variable_registers[y as usize]);
-        if overflow {
-            self.set_flag_register(1);
-        } else {
-            self.set_flag_register(0);
-        }
+        let (result, overflow) =
+            self.variable_registers[x].overflowing_add(self.variable_registers[y]);
+        self.set_with_flag(x, result, overflow);
     }
 
     fn subt_regs_left(&mut self, x: usize, y: usize) {
-        let flag: u8 = if self.variable_registers[x as usize] > self.variable_registers[y as usize]
-        {
-            1
-        } else {
-            0
-        };
-        self.variable_registers[x as usize] =
-            self.variable_registers[x as usize].wrapping_sub(self.variable_registers[y as usize]);
-        self.set_flag_register(flag);
+        let (result, borrow) =
+            self.variable_registers[x].overflowing_sub(self.variable_registers[y]);
+        self.set_with_flag(x, result, !borrow);
     }
 
     fn subt_regs_right(&mut self, x: usize, y: usize) {
-        let flag: u8 = if self.variable_registers[y as usize] > self.variable_registers[x as usize]
-        {
-            1
-        } else {
-            0
-        };
-        self.variable_registers[x as usize] =
-            self.variable_registers[y as usize].wrapping_sub(self.variable_registers[x as usize]);
-        self.set_flag_register(flag);
+        let (result, borrow) =
+            self.variable_registers[y].overflowing_sub(self.variable_registers[x]);
+        self.set_with_flag(x, result, !borrow);
     }
 
     fn skip_if_key(
@@ -294,40 +399,32 @@ impl Cpu {
     }
 
     fn wait_for_key(&mut self, x: usize, keypad: &Keypad, interpreter: &Interpreter) {
-        let current_key: Option<Scancode> = keypad.current_key();
-        if current_key.is_some() {
-            let key_val: Option<u8> = keypad.scancode_to_byte(&current_key.unwrap());
-            if key_val.is_some() {
-                self.variable_registers[x] = key_val.unwrap();
-                return;
-            }
+        if let Some(key_val) = keypad.current_key() {
+            self.variable_registers[x] = key_val;
+            return;
         }
         self.program_counter = interpreter.prev_pc(self.program_counter);
     }
 
     fn add_to_index(&mut self, x: usize) {
         let vx: u16 = self.variable_registers[x] as u16;
-        self.index_register += vx;
+        self.index_register = self.index_register.wrapping_add(vx);
         if self.index_register > MAX_INDEX_REG_VAL {
-            self.index_register %= self.index_register;
+            self.index_register &= MAX_INDEX_REG_VAL;
             if self.config.modern_index_addition() {
                 self.set_flag_register(1);
             }
         }
     }
 
+    // Called once per 60 Hz frame.
     pub fn operate_timers(&mut self) {
-        let elapsed: Duration = self.last_time.elapsed();
-        let rate: Duration = Duration::from_secs_f64(1.0 / TIMER_HZ);
-
-        if elapsed >= rate {
-            if self.delay_timer > 0 {
-                self.delay_timer -= 1;
-            }
-            if self.sound_timer > 0 {
-                self.sound_timer -= 1;
-            }
-            self.last_time += rate;
+        self.random.tick();
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
         }
     }
 
@@ -341,7 +438,11 @@ impl Cpu {
         self.index_register = memory.get_font(font_val);
     }
 
-    fn store_three_decimal_digits(&self, x: usize, memory: &mut Memory) {
+    fn store_three_decimal_digits(
+        &self,
+        x: usize,
+        memory: &mut Memory,
+    ) -> Result<(), EmulatorError> {
         let i: u16 = self.index_register;
 
         let mut value: u8 = self.variable_registers[x];
@@ -351,44 +452,136 @@ impl Cpu {
         value -= dig_2 * 10;
         let dig_1: u8 = value / 100;
 
-        memory.set_heap(i, dig_1);
-        memory.set_heap(i + 1, dig_2);
-        memory.set_heap(i + 2, dig_3);
+        memory.set_heap(i, dig_1)?;
+        memory.set_heap(i.wrapping_add(1), dig_2)?;
+        memory.set_heap(i.wrapping_add(2), dig_3)
     }
 
-    fn store_x_regs(&mut self, reg_count: usize, memory: &mut Memory) {
+    fn store_x_regs(&mut self, reg_count: usize, memory: &mut Memory) -> Result<(), EmulatorError> {
         if self.config.modern_store_and_load() {
             for i in 0..reg_count {
                 let vi: u8 = self.variable_registers[i];
-                memory.set_heap(self.index_register + i as u16, vi);
+                memory.set_heap(self.index_register.wrapping_add(i as u16), vi)?;
             }
         } else {
             for i in 0..reg_count {
                 let vi: u8 = self.variable_registers[i];
-                memory.set_heap(self.index_register, vi);
-                self.index_register += 1;
+                memory.set_heap(self.index_register, vi)?;
+                self.index_register = self.index_register.wrapping_add(1);
             }
         }
+        Ok(())
     }
 
-    fn load_x_regs(&mut self, reg_count: usize, memory: &Memory) {
+    fn load_x_regs(&mut self, reg_count: usize, memory: &Memory) -> Result<(), EmulatorError> {
         if self.config.modern_store_and_load() {
             for i in 0..reg_count {
-                let val: u8 = memory.at_address(self.index_register + i as u16);
+                let val: u8 = memory.at_address(self.index_register.wrapping_add(i as u16))?;
                 self.variable_registers[i] = val;
             }
         } else {
             for i in 0..reg_count {
-                let val: u8 = memory.at_address(self.index_register);
+                let val: u8 = memory.at_address(self.index_register)?;
                 self.variable_registers[i] = val;
-                self.index_register += 1;
+                self.index_register = self.index_register.wrapping_add(1);
             }
         }
+        Ok(())
     }
 
-    fn enter_subroutine(&mut self, nnn: u16, memory: &mut Memory) {
-        memory.push_stack(self.program_counter);
+    fn enter_subroutine(&mut self, nnn: u16, memory: &mut Memory) -> Result<(), EmulatorError> {
+        if let Err(fault) = memory.push_stack(self.program_counter) {
+            self.recover_stack_fault(fault, memory)?;
+            memory.push_stack_wrapping(self.program_counter);
+        }
         self.program_counter = nnn;
+        Ok(())
+    }
+
+    fn return_from_subroutine(&mut self, memory: &mut Memory) -> Result<(), EmulatorError> {
+        match memory.pop_stack() {
+            Ok(address) => self.program_counter = address,
+            Err(fault) => {
+                self.recover_stack_fault(fault, memory)?;
+                self.program_counter = memory.pop_stack_wrapping();
+            }
+        }
+        Ok(())
+    }
+
+    // Returns Ok if execution should go on with a wrapped stack pointer.
+    fn recover_stack_fault(
+        &mut self,
+        fault: StackFault,
+        memory: &Memory,
+    ) -> Result<(), EmulatorError> {
+        match self.config.stack_policy() {
+            StackPolicy::Halt => {
+                self.program_counter = self.instruction_address;
+                Err(EmulatorError::StackFault {
+                    pc: self.instruction_address,
+                    fault,
+                    depth: memory.get_stack_depth(),
+                })
+            }
+            StackPolicy::Wrap => Ok(()),
+            StackPolicy::Debug => {
+                self.break_reason = Some(format!(
+                    "{} at PC 0x{:04X} ({} of {} stack entries in use).",
+                    fault,
+                    self.instruction_address,
+                    memory.get_stack_pointer(),
+                    memory.get_stack_depth()
+                ));
+                Ok(())
+            }
+        }
+    }
+
+    fn handle_unknown_opcode(&mut self) -> Result<(), EmulatorError> {
+        let pc: u16 = self.instruction_address;
+        let opcode: u16 = self.instruction_raw;
+        let entry: &mut UnknownOpcode =
+            self.unknown_opcodes.entry(opcode).or_insert(UnknownOpcode {
+                count: 0,
+                first_pc: pc,
+            });
+        entry.count += 1;
+        match self.config.opcode_policy() {
+            OpcodePolicy::Ignore => Ok(()),
+            OpcodePolicy::Warn => {
+                eprintln!("Warning: unknown opcode {:04X} at PC 0x{:04X}.", opcode, pc);
+                Ok(())
+            }
+            OpcodePolicy::Halt => {
+                self.program_counter = pc;
+                Err(EmulatorError::InvalidOpcode { pc, opcode })
+            }
+            OpcodePolicy::Debug => {
+                self.break_reason =
+                    Some(format!("Unknown opcode {:04X} at PC 0x{:04X}.", opcode, pc));
+                Ok(())
+            }
+        }
+    }
+
+    pub fn print_unknown_opcodes(&self) {
+        if self.unknown_opcodes.is_empty() {
+            return;
+        }
+        let total: u64 = self.unknown_opcodes.values().map(|entry| entry.count).sum();
+        println!(
+            "Unknown opcodes: {} executed, {} distinct",
+            total,
+            self.unknown_opcodes.len()
+        );
+        for (opcode, entry) in &self.unknown_opcodes {
+            println!(
+                "  {:04X}  x{:<8} first at PC 0x{:04X}",
+                opcode, entry.count, entry.first_pc
+            );
+        }
+        println!("The ROM may need a different platform mode or quirk settings.");
     }
 
     fn skip_instruction(
@@ -405,3 +598,209 @@ impl Cpu {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Machine {
+        cpu: Cpu,
+        memory: Memory,
+        display: DisplayScreen,
+        keypad: Keypad,
+        interpreter: Interpreter,
+    }
+
+    impl Machine {
+        fn new(config: CpuConfig, program: &[u8]) -> Machine {
+            let mut memory: Memory = Memory::with_stack_depth(config.stack_depth());
+            memory.load_program(program).unwrap();
+            let mut cpu: Cpu = Cpu::new(config);
+            cpu.point_pc_to_program();
+            Machine {
+                cpu,
+                memory,
+                display: DisplayScreen::new(),
+                keypad: Keypad::new(),
+                interpreter: Interpreter::new(),
+            }
+        }
+
+        fn step(&mut self) -> Result<(), EmulatorError> {
+            let raw: u16 = self.cpu.fetch(&self.memory, &self.interpreter)?;
+            let instruction: CpuInst = self.cpu.decode(raw, &self.interpreter);
+            self.cpu.execute(
+                &instruction,
+                &self.interpreter,
+                &mut self.memory,
+                &mut self.display,
+                &self.keypad,
+            )
+        }
+    }
+
+    // A subroutine at 0x200 that calls itself, two stack entries deep.
+    fn recursion(stack_policy: StackPolicy) -> Machine {
+        let mut config: CpuConfig = CpuConfig::default();
+        config.set_stack_depth(2);
+        config.set_stack_policy(stack_policy);
+        let mut machine: Machine = Machine::new(config, &[0x22, 0x00]);
+        machine.step().unwrap();
+        machine.step().unwrap();
+        machine
+    }
+
+    #[test]
+    fn halt_stops_on_stack_overflow() {
+        let mut machine: Machine = recursion(StackPolicy::Halt);
+        match machine.step() {
+            Err(EmulatorError::StackFault { pc, fault, depth }) => {
+                assert_eq!((pc, fault, depth), (0x200, StackFault::Overflow, 2))
+            }
+            other => panic!("expected a stack fault, got {:?}", other),
+        }
+        assert_eq!(machine.cpu.get_pc(), 0x200);
+        assert_eq!(machine.memory.get_stack(), [0x202, 0x202]);
+    }
+
+    #[test]
+    fn wrap_overwrites_on_stack_overflow() {
+        let mut machine: Machine = recursion(StackPolicy::Wrap);
+        machine.step().unwrap();
+        assert_eq!(machine.memory.get_stack_pointer(), 1);
+        assert_eq!(machine.cpu.take_break_reason(), None);
+    }
+
+    #[test]
+    fn debug_breaks_on_stack_overflow() {
+        let mut machine: Machine = recursion(StackPolicy::Debug);
+        machine.step().unwrap();
+        let reason: String = machine.cpu.take_break_reason().unwrap();
+        assert!(
+            reason.starts_with("Stack overflow at PC 0x0200"),
+            "{}",
+            reason
+        );
+    }
+
+    #[test]
+    fn reset_drops_a_pending_break() {
+        let mut machine: Machine = recursion(StackPolicy::Debug);
+        machine.step().unwrap();
+        machine.cpu.reset();
+        assert_eq!(machine.cpu.take_break_reason(), None);
+    }
+
+    #[test]
+    fn halt_stops_on_stack_underflow() {
+        let mut machine: Machine = Machine::new(CpuConfig::default(), &[0x00, 0xEE]);
+        assert!(matches!(
+            machine.step(),
+            Err(EmulatorError::StackFault {
+                fault: StackFault::Underflow,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn switches_display_modes_and_draws_large_sprites_in_hires() {
+        let mut program: Vec<u8> = vec![0x00, 0xFF, 0xA2, 0x08, 0xD0, 0x10, 0x00, 0xFE];
+        program.extend_from_slice(&[0xFF; 32]);
+        let mut machine: Machine = Machine::new(CpuConfig::default(), &program);
+        machine.step().unwrap();
+        assert!(machine.display.is_hires());
+        machine.step().unwrap();
+        machine.step().unwrap();
+        assert!(machine.display.is_lit(15, 15));
+        assert!(!machine.display.is_lit(16, 0) && !machine.display.is_lit(0, 16));
+        machine.step().unwrap();
+        assert!(!machine.display.is_hires());
+        assert!(!machine.display.is_lit(0, 0));
+    }
+
+    // Runs one instruction with VX and VY set, and returns the registers.
+    fn alu(
+        config: CpuConfig,
+        instruction: CpuInst,
+        x: usize,
+        vx: u8,
+        y: usize,
+        vy: u8,
+    ) -> [u8; VARIABLE_REGISTER_COUNT] {
+        let mut machine: Machine = Machine::new(config, &[]);
+        let mut registers: [u8; VARIABLE_REGISTER_COUNT] = [0; VARIABLE_REGISTER_COUNT];
+        registers[0xF] = 0x55;
+        registers[x] = vx;
+        registers[y] = vy;
+        machine
+            .cpu
+            .restore(PROGRAM_ADDRESS as u16, 0, registers, 0, 0, false);
+        machine
+            .cpu
+            .execute(
+                &instruction,
+                &machine.interpreter,
+                &mut machine.memory,
+                &mut machine.display,
+                &machine.keypad,
+            )
+            .unwrap();
+        *machine.cpu.get_variable_registers()
+    }
+
+    #[test]
+    fn logic_ops_reset_vf_only_with_the_quirk() {
+        let mut vf_reset: CpuConfig = CpuConfig::default();
+        vf_reset.toggle_quirk(Quirk::VfReset);
+        for (config, flag) in [(CpuConfig::default(), 0x55), (vf_reset, 0)] {
+            let cases: [(CpuInst, u8); 3] = [
+                (CpuInst::BitOrXY(1, 2), 0xFC),
+                (CpuInst::BitAndXY(1, 2), 0x30),
+                (CpuInst::BitXorXY(1, 2), 0xCC),
+            ];
+            for (instruction, result) in cases {
+                let registers = alu(config, instruction, 1, 0xF0, 2, 0x3C);
+                assert_eq!((registers[1], registers[0xF]), (result, flag));
+            }
+        }
+    }
+
+    // With X being F, the flag overwrites the result.
+    #[test]
+    fn flag_wins_when_x_is_vf() {
+        let config: CpuConfig = CpuConfig::default();
+        let cases: [(CpuInst, u8, u8, u8); 7] = [
+            (CpuInst::AddXY(0xF, 1), 0xFF, 0x01, 1),
+            (CpuInst::AddXY(0xF, 1), 0x01, 0x01, 0),
+            (CpuInst::SubtFromLeftXY(0xF, 1), 0x05, 0x03, 1),
+            (CpuInst::SubtFromLeftXY(0xF, 1), 0x03, 0x05, 0),
+            (CpuInst::SubtFromRightXY(0xF, 1), 0x03, 0x05, 1),
+            (CpuInst::ShiftRightXY(0xF, 1), 0x03, 0x00, 1),
+            (CpuInst::ShiftLeftXY(0xF, 1), 0x81, 0x00, 1),
+        ];
+        for (instruction, vf, vy, flag) in cases {
+            let name: String = format!("{:?} with VF={:02X} VY={:02X}", instruction, vf, vy);
+            assert_eq!(
+                alu(config, instruction, 0xF, vf, 1, vy)[0xF],
+                flag,
+                "{}",
+                name
+            );
+        }
+        let registers = alu(config, CpuInst::ShiftLeftXY(0xF, 1), 0xF, 0x01, 1, 0);
+        assert_eq!(registers[0xF], 0);
+    }
+
+    #[test]
+    fn subtracting_equal_values_does_not_borrow() {
+        let config: CpuConfig = CpuConfig::default();
+        for instruction in [
+            CpuInst::SubtFromLeftXY(1, 2),
+            CpuInst::SubtFromRightXY(1, 2),
+        ] {
+            let registers = alu(config, instruction, 1, 0x42, 2, 0x42);
+            assert_eq!((registers[1], registers[0xF]), (0, 1));
+        }
+    }
+}
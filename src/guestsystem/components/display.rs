@@ -1,104 +1,309 @@
-use std::{thread, time::Duration};
-
-use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window, Sdl, VideoSubsystem};
-
 use super::cpu::Cpu;
 
-const COLOR_OFF: Color = Color::BLACK;
-const COLOR_ON: Color = Color::WHITE;
-const WIDTH: u8 = 64;
-const HEIGHT: u8 = 32;
-const SIZE_MULTIPLIER: u8 = 10;
-const REFRESH_FPS: f64 = 160.0;
+// The framebuffer is as large as the SUPER-CHIP hires screen. In lores mode
+// every pixel of the 64x32 screen covers a block of 2x2 framebuffer pixels.
+pub const WIDTH: u8 = 128;
+pub const HEIGHT: u8 = 64;
+pub const LORES_WIDTH: u8 = 64;
+pub const LORES_HEIGHT: u8 = 32;
 
-pub struct DisplayScreen<'a> {
-    context: &'a Sdl,
-    canvas: Canvas<Window>,
+pub struct DisplayScreen {
     pixels: [[bool; HEIGHT as usize]; WIDTH as usize],
+    hires: bool,
 }
 
-impl<'a> DisplayScreen<'a> {
-    pub fn new(context: &Sdl) -> DisplayScreen {
-        let video_subsystem: VideoSubsystem = context.video().unwrap();
-        let window: Window = video_subsystem
-            .window(
-                "RCHIP-8",
-                WIDTH as u32 * SIZE_MULTIPLIER as u32,
-                HEIGHT as u32 * SIZE_MULTIPLIER as u32,
-            )
-            .position_centered()
-            .build()
-            .unwrap();
-        let canvas: Canvas<Window> = window.into_canvas().build().unwrap();
+impl DisplayScreen {
+    pub fn new() -> DisplayScreen {
         DisplayScreen {
-            context,
-            canvas,
             pixels: [[false; HEIGHT as usize]; WIDTH as usize],
+            hires: false,
+        }
+    }
+
+    pub fn get_pixels(&self) -> &[[bool; HEIGHT as usize]; WIDTH as usize] {
+        &self.pixels
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, lit: bool) {
+        self.pixels[x][y] = lit;
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    // Switching modes clears the screen, as on the SUPER-CHIP and XO-CHIP.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.clear_screen();
+    }
+
+    // The screen size of the current mode.
+    pub fn get_size(&self) -> (usize, usize) {
+        if self.hires {
+            (WIDTH as usize, HEIGHT as usize)
+        } else {
+            (LORES_WIDTH as usize, LORES_HEIGHT as usize)
+        }
+    }
+
+    // Whether a pixel of the current mode is lit.
+    pub fn is_lit(&self, x: usize, y: usize) -> bool {
+        let block: usize = self.block_size();
+        self.pixels[x * block][y * block]
+    }
+
+    fn block_size(&self) -> usize {
+        if self.hires {
+            1
+        } else {
+            2
         }
     }
 
     pub fn clear_screen(&mut self) {
-        for mut col in self.pixels {
+        for col in self.pixels.iter_mut() {
             col.fill(false);
         }
-        self.canvas.set_draw_color(COLOR_OFF);
-        self.canvas.clear();
-        thread::sleep(Duration::from_secs_f64(1.0 / REFRESH_FPS));
-        self.canvas.present();
     }
 
-    pub fn display(&mut self, x_coord: u8, y_coord: u8, sprite: &[u8], cpu: &mut Cpu) {
-        let mut y: u8 = y_coord % HEIGHT;
+    // Sprites are 8 pixels wide, or 16 for the 16x16 sprites of DXY0. The
+    // start position always wraps. The rest of the sprite is clipped at the
+    // right and bottom edges, or wraps around to the other side. Both work the
+    // same in lores and hires, on the screen size of the current mode.
+    pub fn display(
+        &mut self,
+        x_coord: u8,
+        y_coord: u8,
+        sprite: &[u8],
+        sprite_width: usize,
+        wrap: bool,
+        cpu: &mut Cpu,
+    ) {
+        let (width, height) = self.get_size();
+        let block: usize = self.block_size();
+        let start_x: usize = x_coord as usize % width;
+        let start_y: usize = y_coord as usize % height;
+        let row_bytes: usize = sprite_width / 8;
         cpu.set_flag_register(0);
 
-        for data in sprite {
-            let mut x: u8 = x_coord % WIDTH;
-            let mut byte: u8 = data.clone();
-            for _ in 0..8 {
-                let bit: bool = byte & 0x80 == 0x80;
-                let pixel: bool = self.pixels[x as usize][y as usize];
-                if bit && pixel {
-                    self.erase_pixel(x, y);
+        for (row, data) in sprite.chunks(row_bytes).enumerate() {
+            let mut y: usize = start_y + row;
+            if y >= height {
+                if !wrap {
+                    break;
+                }
+                y %= height;
+            }
+            let bits: u16 = data.iter().fold(0, |bits, byte| bits << 8 | *byte as u16);
+            for bit in 0..sprite_width {
+                let mut x: usize = start_x + bit;
+                if x >= width {
+                    if !wrap {
+                        break;
+                    }
+                    x %= width;
+                }
+                if bits & (1 << (sprite_width - 1 - bit)) == 0 {
+                    continue;
+                }
+                if self.pixels[x * block][y * block] {
                     cpu.set_flag_register(1);
-                } else if bit && !pixel {
-                    self.draw_pixel(x, y);
                 }
-                if x == WIDTH - 1 {
-                    break;
+                for column in &mut self.pixels[x * block..(x + 1) * block] {
+                    for pixel in &mut column[y * block..(y + 1) * block] {
+                        *pixel = !*pixel;
+                    }
                 }
-                x += 1;
-                byte <<= 1;
             }
-            if y == HEIGHT - 1 {
-                break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CpuConfig;
+
+    // Draws the sprite and returns VF.
+    fn draw(display: &mut DisplayScreen, x: u8, y: u8, sprite: &[u8], wrap: bool) -> u8 {
+        let mut cpu: Cpu = Cpu::new(CpuConfig::default());
+        display.display(x, y, sprite, 8, wrap, &mut cpu);
+        cpu.get_variable_registers()[0xF]
+    }
+
+    // The lit pixels in the coordinates of the current mode.
+    fn lit_pixels(display: &DisplayScreen) -> Vec<(usize, usize)> {
+        let (width, height) = display.get_size();
+        let mut lit: Vec<(usize, usize)> = Vec::new();
+        for x in 0..width {
+            for y in 0..height {
+                if display.is_lit(x, y) {
+                    lit.push((x, y));
+                }
             }
-            y += 1;
         }
+        lit
+    }
+
+    fn hires() -> DisplayScreen {
+        let mut display: DisplayScreen = DisplayScreen::new();
+        display.set_hires(true);
+        display
+    }
+
+    #[test]
+    fn clips_at_the_right_edge() {
+        let mut display: DisplayScreen = DisplayScreen::new();
+        assert_eq!(draw(&mut display, 62, 5, &[0xFF], false), 0);
+        assert_eq!(lit_pixels(&display), [(62, 5), (63, 5)]);
+    }
+
+    #[test]
+    fn wraps_at_the_right_edge() {
+        let mut display: DisplayScreen = DisplayScreen::new();
+        assert_eq!(draw(&mut display, 62, 5, &[0xFF], true), 0);
+        assert_eq!(
+            lit_pixels(&display),
+            [
+                (0, 5),
+                (1, 5),
+                (2, 5),
+                (3, 5),
+                (4, 5),
+                (5, 5),
+                (62, 5),
+                (63, 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn clips_at_the_bottom_edge() {
+        let mut display: DisplayScreen = DisplayScreen::new();
+        assert_eq!(draw(&mut display, 7, 30, &[0x80; 4], false), 0);
+        assert_eq!(lit_pixels(&display), [(7, 30), (7, 31)]);
+    }
+
+    #[test]
+    fn wraps_at_the_bottom_edge() {
+        let mut display: DisplayScreen = DisplayScreen::new();
+        assert_eq!(draw(&mut display, 7, 30, &[0x80; 4], true), 0);
+        assert_eq!(lit_pixels(&display), [(7, 0), (7, 1), (7, 30), (7, 31)]);
+    }
+
+    #[test]
+    fn clips_at_the_corner() {
+        let mut display: DisplayScreen = DisplayScreen::new();
+        assert_eq!(draw(&mut display, 63, 31, &[0xC0, 0xC0], false), 0);
+        assert_eq!(lit_pixels(&display), [(63, 31)]);
+    }
+
+    #[test]
+    fn wraps_at_the_corner() {
+        let mut display: DisplayScreen = DisplayScreen::new();
+        assert_eq!(draw(&mut display, 63, 31, &[0xC0, 0xC0], true), 0);
+        assert_eq!(lit_pixels(&display), [(0, 0), (0, 31), (63, 0), (63, 31)]);
+    }
+
+    #[test]
+    fn always_wraps_the_start_position() {
+        for wrap in [false, true] {
+            let mut display: DisplayScreen = DisplayScreen::new();
+            draw(&mut display, 66, 33, &[0x80], wrap);
+            assert_eq!(lit_pixels(&display), [(2, 1)]);
+        }
+    }
+
+    #[test]
+    fn sets_vf_on_collisions_with_wrapped_pixels() {
+        let mut display: DisplayScreen = DisplayScreen::new();
+        draw(&mut display, 63, 31, &[0xC0, 0xC0], true);
+        assert_eq!(draw(&mut display, 0, 0, &[0x80], true), 1);
+        assert_eq!(lit_pixels(&display), [(0, 31), (63, 0), (63, 31)]);
+        assert_eq!(draw(&mut display, 10, 10, &[0x80], true), 0);
+    }
+
+    #[test]
+    fn clipped_pixels_do_not_collide() {
+        let mut display: DisplayScreen = DisplayScreen::new();
+        draw(&mut display, 0, 0, &[0x80], false);
+        assert_eq!(draw(&mut display, 63, 31, &[0xC0, 0xC0], false), 0);
+        assert_eq!(lit_pixels(&display), [(0, 0), (63, 31)]);
+        assert_eq!(draw(&mut display, 62, 31, &[0x40], false), 1);
+        assert_eq!(lit_pixels(&display), [(0, 0)]);
+    }
+
+    #[test]
+    fn lores_pixels_cover_two_by_two_blocks() {
+        let mut display: DisplayScreen = DisplayScreen::new();
+        draw(&mut display, 63, 31, &[0x80], false);
+        let lit: usize = display
+            .get_pixels()
+            .iter()
+            .flatten()
+            .filter(|p| **p)
+            .count();
+        assert_eq!(lit, 4);
+        assert!(display.get_pixels()[127][63] && display.get_pixels()[126][62]);
+    }
+
+    #[test]
+    fn clips_at_the_hires_edges() {
+        let mut display: DisplayScreen = hires();
+        assert_eq!(draw(&mut display, 126, 62, &[0xFF; 4], false), 0);
+        assert_eq!(
+            lit_pixels(&display),
+            [(126, 62), (126, 63), (127, 62), (127, 63)]
+        );
+    }
+
+    #[test]
+    fn wraps_at_the_hires_edges() {
+        let mut display: DisplayScreen = hires();
+        assert_eq!(draw(&mut display, 127, 63, &[0xC0, 0xC0], true), 0);
+        assert_eq!(lit_pixels(&display), [(0, 0), (0, 63), (127, 0), (127, 63)]);
+    }
+
+    #[test]
+    fn hires_wraps_the_start_position_on_its_own_size() {
+        for wrap in [false, true] {
+            let mut display: DisplayScreen = hires();
+            draw(&mut display, 130, 66, &[0x80], wrap);
+            assert_eq!(lit_pixels(&display), [(2, 2)]);
+        }
+    }
+
+    #[test]
+    fn draws_sixteen_pixel_wide_sprites() {
+        let mut cpu: Cpu = Cpu::new(CpuConfig::default());
+        let mut display: DisplayScreen = hires();
+        display.display(120, 0, &[0x80, 0x01, 0xFF, 0xFF], 16, false, &mut cpu);
+        assert_eq!(
+            lit_pixels(&display),
+            [
+                (120, 0),
+                (120, 1),
+                (121, 1),
+                (122, 1),
+                (123, 1),
+                (124, 1),
+                (125, 1),
+                (126, 1),
+                (127, 1)
+            ]
+        );
+        display.display(120, 63, &[0x80, 0x01, 0x80, 0x01], 16, true, &mut cpu);
+        assert_eq!(cpu.get_variable_registers()[0xF], 1);
+        assert!(display.is_lit(7, 63) && display.is_lit(7, 0) && !display.is_lit(120, 0));
     }
 
-    fn draw_pixel(&mut self, x: u8, y: u8) {
-        self.pixels[x as usize][y as usize] = true;
-        self.canvas.set_draw_color(COLOR_ON);
-        let x_canv: i32 = (x as i32) * (SIZE_MULTIPLIER as i32);
-        let y_canv: i32 = (y as i32) * (SIZE_MULTIPLIER as i32);
-        let px_size: u32 = SIZE_MULTIPLIER as u32;
-        self.canvas
-            .fill_rect(Rect::new(x_canv, y_canv, px_size, px_size))
-            .expect("Error while drawing pixel on display.");
-        thread::sleep(Duration::from_secs_f64(1.0 / REFRESH_FPS));
-        self.canvas.present();
-    }
-
-    fn erase_pixel(&mut self, x: u8, y: u8) {
-        self.pixels[x as usize][y as usize] = false;
-        self.canvas.set_draw_color(COLOR_OFF);
-        let x_canv: i32 = (x as i32) * (SIZE_MULTIPLIER as i32);
-        let y_canv: i32 = (y as i32) * (SIZE_MULTIPLIER as i32);
-        let px_size: u32 = SIZE_MULTIPLIER as u32;
-        self.canvas
-            .fill_rect(Rect::new(x_canv, y_canv, px_size, px_size))
-            .expect("Error while erasing pixel on display.");
-        thread::sleep(Duration::from_secs_f64(1.0 / REFRESH_FPS));
-        self.canvas.present();
+    #[test]
+    fn switching_modes_clears_the_screen() {
+        let mut display: DisplayScreen = DisplayScreen::new();
+        draw(&mut display, 1, 1, &[0x80], false);
+        display.set_hires(true);
+        assert_eq!(lit_pixels(&display), []);
+        assert_eq!(display.get_size(), (128, 64));
     }
 }
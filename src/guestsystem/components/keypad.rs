@@ -1,118 +1,63 @@
-use sdl2::{keyboard::Scancode, Sdl};
+pub const KEY_COUNT: usize = 16;
 
-pub struct Keypad<'a> {
-    context: &'a Sdl,
-    current_key: Option<Scancode>,
-    released_key: Option<Scancode>,
-    last_key_val: u8,
-    last_released_key_val: u8,
+pub struct Keypad {
+    pressed: [bool; KEY_COUNT],
+    current_key: Option<u8>,
+    released_key: Option<u8>,
 }
 
-impl<'a> Keypad<'a> {
-    pub fn new(context: &Sdl) -> Keypad {
+impl Keypad {
+    pub fn new() -> Keypad {
         Keypad {
-            context,
+            pressed: [false; KEY_COUNT],
             current_key: None,
             released_key: None,
-            last_key_val: 0xff,
-            last_released_key_val: 0xff,
         }
     }
 
-    //  QWERTY  EMULATED
-    // 1 2 3 4 | 1 2 3 C
-    // Q W E R | 4 5 6 D
-    // A S D F | 7 8 9 E
-    // Z X C V | A 0 B F
-    pub fn scancode_to_byte(&self, scancode: &Scancode) -> Option<u8> {
-        match scancode {
-            Scancode::Num1 => Some(0x1),
-            Scancode::Num2 => Some(0x2),
-            Scancode::Num3 => Some(0x3),
-            Scancode::Num4 => Some(0xC),
-            Scancode::Q => Some(0x4),
-            Scancode::W => Some(0x5),
-            Scancode::E => Some(0x6),
-            Scancode::R => Some(0xD),
-            Scancode::A => Some(0x7),
-            Scancode::S => Some(0x8),
-            Scancode::D => Some(0x9),
-            Scancode::F => Some(0xE),
-            Scancode::Z => Some(0xA),
-            Scancode::X => Some(0x0),
-            Scancode::C => Some(0xB),
-            Scancode::V => Some(0xF),
-            _ => None,
-        }
+    pub fn set_current_key(&mut self, key_val: u8) {
+        self.pressed[key_val as usize & 0x0F] = true;
+        self.current_key = Some(key_val & 0x0F);
     }
 
-    pub fn byte_to_scancode(&self, key_val: u8) -> Option<Scancode> {
-        match key_val {
-            0x0 => Some(Scancode::X),
-            0x1 => Some(Scancode::Num1),
-            0x2 => Some(Scancode::Num2),
-            0x3 => Some(Scancode::Num3),
-            0x4 => Some(Scancode::Q),
-            0x5 => Some(Scancode::W),
-            0x6 => Some(Scancode::E),
-            0x7 => Some(Scancode::A),
-            0x8 => Some(Scancode::S),
-            0x9 => Some(Scancode::D),
-            0xA => Some(Scancode::Z),
-            0xB => Some(Scancode::C),
-            0xC => Some(Scancode::Num4),
-            0xD => Some(Scancode::R),
-            0xE => Some(Scancode::F),
-            0xF => Some(Scancode::V),
-            _ => None,
-        }
+    pub fn same_current_key_val(&self, key_val: u8) -> bool {
+        self.pressed[key_val as usize & 0x0F]
     }
 
-    pub fn set_current_key(&mut self, scancode: Option<Scancode>) {
-        if let Some(key) = scancode {
-            if let Some(val) = self.scancode_to_byte(&key) {
-                self.last_key_val = val;
-            }
-        }
-        self.current_key = scancode
+    pub fn current_key(&self) -> Option<u8> {
+        self.current_key
     }
 
-    pub fn same_current_key_val(&self, key_val: u8) -> bool {
-        match self.current_key {
-            Some(scancode) => {
-                let current_val: Option<u8> = self.scancode_to_byte(&scancode);
-                if current_val.is_none() {
-                    return false;
-                }
-                current_val.unwrap() == key_val
-            }
-            None => false,
+    // Releasing the most recent key falls back to any other key still held.
+    pub fn set_released_key(&mut self, key_val: u8) {
+        let key_val: u8 = key_val & 0x0F;
+        self.pressed[key_val as usize] = false;
+        self.released_key = Some(key_val);
+        if self.current_key == Some(key_val) {
+            self.current_key = (0..KEY_COUNT as u8).find(|key| self.pressed[*key as usize]);
         }
     }
 
-    pub fn current_key(&self) -> Option<Scancode> {
-        self.current_key
+    // The held keys as a bit mask, key 0 in the lowest bit.
+    pub fn get_pressed(&self) -> u16 {
+        (0..KEY_COUNT)
+            .filter(|key| self.pressed[*key])
+            .fold(0, |mask, key| mask | 1 << key)
     }
 
-    pub fn set_released_key(&mut self, released_key: Option<Scancode>) {
-        if let Some(key) = released_key {
-            if let Some(val) = self.scancode_to_byte(&key) {
-                self.last_released_key_val = val;
-            }
+    pub fn get_released_key(&self) -> Option<u8> {
+        self.released_key
+    }
+
+    pub fn restore(&mut self, pressed: u16, current_key: Option<u8>, released_key: Option<u8>) {
+        for (key, held) in self.pressed.iter_mut().enumerate() {
+            *held = pressed & 1 << key != 0;
         }
-        self.released_key = released_key;
+        self.current_key = current_key.map(|key_val| key_val & 0x0F);
+        self.released_key = released_key.map(|key_val| key_val & 0x0F);
     }
 
     pub fn same_released_key_val(&self, key_val: u8) -> bool {
-        match self.released_key {
-            Some(scancode) => {
-                let current_val: Option<u8> = self.scancode_to_byte(&scancode);
-                if current_val.is_none() {
-                    return false;
-                }
-                current_val.unwrap() == key_val
-            }
-            None => false,
-        }
+        self.released_key == Some(key_val & 0x0F)
     }
 }
@@ -1,25 +1,55 @@
-use crate::logic::interpreter::{ALL_FONT_COUNT, FONT_SIZE};
+use std::fmt;
 
-const FOUR_KIBI: usize = 4096;
+use crate::{
+    error::EmulatorError,
+    logic::interpreter::{ALL_FONT_COUNT, FONT_SIZE},
+};
+
+pub const FOUR_KIBI: usize = 4096;
 pub const PROGRAM_ADDRESS: usize = 0x200;
-const FONTS_ADDRESS: usize = 0x050;
+pub const FONTS_ADDRESS: usize = 0x050;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StackFault {
+    Overflow,
+    Underflow,
+}
+
+impl fmt::Display for StackFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StackFault::Overflow => write!(f, "Stack overflow"),
+            StackFault::Underflow => write!(f, "Stack underflow"),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Memory {
     heap: [u8; FOUR_KIBI],
     stack: Vec<u16>,
+    stack_pointer: usize,
 }
 
 impl Memory {
-    pub fn new() -> Memory {
+    pub fn with_stack_depth(stack_depth: usize) -> Memory {
         Memory {
             heap: [0; FOUR_KIBI],
-            stack: Vec::new(),
+            stack: vec![0; stack_depth],
+            stack_pointer: 0,
         }
     }
 
-    pub fn load_program(&mut self, program: &[u8]) {
+    pub fn load_program(&mut self, program: &[u8]) -> Result<(), EmulatorError> {
+        let max: usize = FOUR_KIBI - PROGRAM_ADDRESS;
+        if program.len() > max {
+            return Err(EmulatorError::RomTooLarge {
+                size: program.len(),
+                max,
+            });
+        }
         self.load(&program, PROGRAM_ADDRESS);
+        Ok(())
     }
 
     pub fn load_fonts(&mut self, fonts: [u8; FONT_SIZE * ALL_FONT_COUNT]) {
@@ -36,24 +66,91 @@ impl Memory {
         }
     }
 
-    pub fn at_address(&self, address: u16) -> u8 {
-        self.heap[address as usize]
+    pub fn at_address(&self, address: u16) -> Result<u8, EmulatorError> {
+        match self.heap.get(address as usize) {
+            Some(byte) => Ok(*byte),
+            None => Err(EmulatorError::MemoryFault {
+                address: address as usize,
+            }),
+        }
+    }
+
+    pub fn get_heap(&self) -> &[u8] {
+        &self.heap
     }
 
     pub fn get_heap_size(&self) -> usize {
         self.heap.len()
     }
 
-    pub fn get_heap_slice(&self, from: u16, length: u16) -> &[u8] {
-        &self.heap[from as usize..from as usize + length as usize]
+    pub fn get_heap_slice(&self, from: u16, length: u16) -> Result<&[u8], EmulatorError> {
+        let end: usize = from as usize + length as usize;
+        if end > FOUR_KIBI {
+            return Err(EmulatorError::MemoryFault {
+                address: (from as usize).max(FOUR_KIBI),
+            });
+        }
+        Ok(&self.heap[from as usize..end])
     }
 
-    pub fn push_stack(&mut self, arg: u16) {
-        self.stack.push(arg);
+    pub fn push_stack(&mut self, arg: u16) -> Result<(), StackFault> {
+        if self.stack_pointer == self.stack.len() {
+            return Err(StackFault::Overflow);
+        }
+        self.stack[self.stack_pointer] = arg;
+        self.stack_pointer += 1;
+        Ok(())
     }
 
-    pub fn pop_stack(&mut self) -> Option<u16> {
-        self.stack.pop()
+    pub fn pop_stack(&mut self) -> Result<u16, StackFault> {
+        if self.stack_pointer == 0 {
+            return Err(StackFault::Underflow);
+        }
+        self.stack_pointer -= 1;
+        Ok(self.stack[self.stack_pointer])
+    }
+
+    // The stack pointer wraps around like a ring buffer, overwriting the
+    // oldest entry on overflow and returning a stale one on underflow.
+    pub fn push_stack_wrapping(&mut self, arg: u16) {
+        let depth: usize = self.stack.len();
+        self.stack[self.stack_pointer % depth] = arg;
+        self.stack_pointer = self.stack_pointer % depth + 1;
+    }
+
+    pub fn pop_stack_wrapping(&mut self) -> u16 {
+        let depth: usize = self.stack.len();
+        self.stack_pointer = (self.stack_pointer + depth - 1) % depth;
+        self.stack[self.stack_pointer]
+    }
+
+    pub fn get_stack_pointer(&self) -> usize {
+        self.stack_pointer
+    }
+
+    pub fn get_stack_depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn get_stack(&self) -> &[u16] {
+        &self.stack[..self.stack_pointer]
+    }
+
+    pub fn clear(&mut self) {
+        self.heap.fill(0);
+        self.stack.fill(0);
+        self.stack_pointer = 0;
+    }
+
+    pub fn restore(&mut self, heap: &[u8], stack: &[u16]) -> Result<(), String> {
+        if heap.len() != FOUR_KIBI || stack.len() > self.stack.len() {
+            return Err("The state does not fit this machine.".to_string());
+        }
+        self.clear();
+        self.heap.copy_from_slice(heap);
+        self.stack[..stack.len()].copy_from_slice(stack);
+        self.stack_pointer = stack.len();
+        Ok(())
     }
 
     pub fn get_font(&self, font_val: u8) -> u16 {
@@ -61,7 +158,48 @@ impl Memory {
         addr as u16
     }
 
-    pub fn set_heap(&mut self, address: u16, byte: u8) {
-        self.heap[address as usize] = byte;
+    pub fn set_heap(&mut self, address: u16, byte: u8) -> Result<(), EmulatorError> {
+        match self.heap.get_mut(address as usize) {
+            Some(cell) => {
+                *cell = byte;
+                Ok(())
+            }
+            None => Err(EmulatorError::MemoryFault {
+                address: address as usize,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_and_pop_use_the_configured_depth() {
+        let mut memory: Memory = Memory::with_stack_depth(3);
+        assert_eq!(memory.get_stack_depth(), 3);
+        for address in [0x200, 0x300, 0x400] {
+            memory.push_stack(address).unwrap();
+        }
+        assert_eq!(memory.push_stack(0x500), Err(StackFault::Overflow));
+        assert_eq!(memory.get_stack(), [0x200, 0x300, 0x400]);
+        assert_eq!(memory.pop_stack(), Ok(0x400));
+        assert_eq!(memory.pop_stack(), Ok(0x300));
+        assert_eq!(memory.pop_stack(), Ok(0x200));
+        assert_eq!(memory.pop_stack(), Err(StackFault::Underflow));
+        assert_eq!(memory.get_stack_pointer(), 0);
+    }
+
+    #[test]
+    fn wrapping_overwrites_the_oldest_entry() {
+        let mut memory: Memory = Memory::with_stack_depth(2);
+        memory.push_stack(0x200).unwrap();
+        memory.push_stack(0x300).unwrap();
+        memory.push_stack_wrapping(0x400);
+        assert_eq!(memory.get_stack(), [0x400]);
+        assert_eq!(memory.pop_stack_wrapping(), 0x400);
+        assert_eq!(memory.pop_stack_wrapping(), 0x300);
+        assert_eq!(memory.get_stack_pointer(), 1);
     }
 }
@@ -0,0 +1,199 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::memory::Memory;
+
+pub const RANDOM_KINDS: [&str; 2] = ["std", "vip"];
+
+// The VIP keeps the second half of its interpreter in this page.
+const VIP_PAGE: u16 = 0x100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RandomKind {
+    Std,
+    Vip,
+}
+
+impl RandomKind {
+    pub fn from_name(name: &str) -> Option<RandomKind> {
+        match name.to_lowercase().as_str() {
+            "std" => Some(RandomKind::Std),
+            "vip" => Some(RandomKind::Vip),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RandomKind::Std => "std",
+            RandomKind::Vip => "vip",
+        }
+    }
+}
+
+// The random number source behind CXNN, seeded so runs can be reproduced.
+pub struct RandomSource {
+    kind: RandomKind,
+    seed: u64,
+    rng: StdRng,
+    vip_state: u16,
+    draws: u64,
+}
+
+impl RandomSource {
+    pub fn new(kind: RandomKind, seed: u64) -> RandomSource {
+        RandomSource {
+            kind,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            vip_state: seed as u16,
+            draws: 0,
+        }
+    }
+
+    // Brings a source back to where a save state left it. The generator state
+    // of std cannot be read out, so the numbers drawn so far are drawn again.
+    pub fn resume(kind: RandomKind, seed: u64, draws: u64, vip_state: u16) -> RandomSource {
+        let mut random: RandomSource = RandomSource::new(kind, seed);
+        if kind == RandomKind::Std {
+            for _ in 0..draws {
+                random.rng.gen::<u8>();
+            }
+        }
+        random.vip_state = vip_state;
+        random.draws = draws;
+        random
+    }
+
+    pub fn get_kind(&self) -> RandomKind {
+        self.kind
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_draws(&self) -> u64 {
+        self.draws
+    }
+
+    pub fn get_vip_state(&self) -> u16 {
+        self.vip_state
+    }
+
+    // Called once per 60 Hz frame, like the INC R9 in the VIP's interrupt routine.
+    pub fn tick(&mut self) {
+        self.vip_state = self.vip_state.wrapping_add(1);
+    }
+
+    pub fn next(&mut self, memory: &Memory) -> u8 {
+        self.draws += 1;
+        match self.kind {
+            RandomKind::Std => self.rng.gen(),
+            RandomKind::Vip => self.next_vip(memory),
+        }
+    }
+
+    // The CXNN routine of the VIP interpreter, with R9 as vip_state:
+    //
+    //   INC R9; GLO R9; PLO RE; GHI R3; PHI RE   RE = 0x100 + R9.0
+    //   GHI R9; SEX RE; ADD                      D = R9.1 + M(RE)
+    //   STR R6; SHRC; SEX R6; ADD                D = D + (D >> 1 | DF << 7)
+    //   PHI R9; STR R6                           R9.1 = VX = D
+    //
+    // The page at 0x100 holds the VIP's interpreter code. This emulator keeps
+    // no interpreter there, so the bytes read are whatever the program left in
+    // it, and the numbers follow the VIP's arithmetic but not its sequence.
+    fn next_vip(&mut self, memory: &Memory) -> u8 {
+        self.vip_state = self.vip_state.wrapping_add(1);
+        let [high, low] = self.vip_state.to_be_bytes();
+        let byte: u8 = memory.at_address(VIP_PAGE + low as u16).unwrap_or(0);
+        let (sum, carry) = high.overflowing_add(byte);
+        let shifted: u8 = sum >> 1 | (carry as u8) << 7;
+        let high: u8 = sum.wrapping_add(shifted);
+        self.vip_state = u16::from_be_bytes([high, low]);
+        high
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_with_page(page: &[u8]) -> Memory {
+        let mut memory: Memory = Memory::with_stack_depth(16);
+        for (offset, byte) in page.iter().enumerate() {
+            memory.set_heap(VIP_PAGE + offset as u16, *byte).unwrap();
+        }
+        memory
+    }
+
+    fn sequence(random: &mut RandomSource, memory: &Memory) -> Vec<u8> {
+        (0..32).map(|_| random.next(memory)).collect()
+    }
+
+    #[test]
+    fn std_repeats_for_the_same_seed() {
+        let memory: Memory = memory_with_page(&[]);
+        let first: Vec<u8> = sequence(&mut RandomSource::new(RandomKind::Std, 1234), &memory);
+        let second: Vec<u8> = sequence(&mut RandomSource::new(RandomKind::Std, 1234), &memory);
+        let other: Vec<u8> = sequence(&mut RandomSource::new(RandomKind::Std, 1235), &memory);
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+
+    // Worked through by hand from the VIP routine, starting with R9 = 0x1200:
+    //   0x12 + M(0x101) 0x35 = 0x47, + 0x23 = 0x6A
+    //   0x6A + M(0x102) 0xC8 = 0x32 carry, + 0x99 = 0xCB
+    //   a frame passes, so R9.0 skips 0x03
+    //   0xCB + M(0x104) 0x00 = 0xCB, + 0x65 = 0x30
+    #[test]
+    fn vip_gives_the_sequence_of_the_vip_routine() {
+        let memory: Memory = memory_with_page(&[0x00, 0x35, 0xC8, 0x07]);
+        let mut random: RandomSource = RandomSource::new(RandomKind::Vip, 0x1200);
+        assert_eq!(random.next(&memory), 0x6A);
+        assert_eq!(random.next(&memory), 0xCB);
+        random.tick();
+        assert_eq!(random.next(&memory), 0x30);
+        assert_eq!(random.get_vip_state(), 0x3004);
+    }
+
+    #[test]
+    fn vip_pointer_carries_into_the_high_byte() {
+        let memory: Memory = memory_with_page(&[0x40]);
+        let mut random: RandomSource = RandomSource::new(RandomKind::Vip, 0x00FF);
+        // R9 becomes 0x0100, so M(0x100) is added to 0x01: 0x41, + 0x20 = 0x61.
+        assert_eq!(random.next(&memory), 0x61);
+        assert_eq!(random.get_vip_state(), 0x6100);
+    }
+
+    #[test]
+    fn resume_continues_the_sequence() {
+        let memory: Memory = memory_with_page(&[0x10, 0x20, 0x30]);
+        for kind in [RandomKind::Std, RandomKind::Vip] {
+            let mut random: RandomSource = RandomSource::new(kind, 99);
+            sequence(&mut random, &memory);
+            random.tick();
+            let mut resumed: RandomSource = RandomSource::resume(
+                kind,
+                random.get_seed(),
+                random.get_draws(),
+                random.get_vip_state(),
+            );
+            assert_eq!(
+                sequence(&mut random, &memory),
+                sequence(&mut resumed, &memory)
+            );
+        }
+    }
+
+    #[test]
+    fn parses_kind_names() {
+        for name in RANDOM_KINDS {
+            assert_eq!(
+                RandomKind::from_name(name).map(|kind| kind.name()),
+                Some(name)
+            );
+        }
+        assert_eq!(RandomKind::from_name("memory"), None);
+    }
+}
@@ -1,71 +1,515 @@
-use sdl2::{event::Event, keyboard::Keycode, Sdl};
+use std::{
+    mem, thread,
+    time::{Duration, Instant},
+};
 
-use crate::logic::interpreter::Interpreter;
+use crate::{
+    error::EmulatorError,
+    host::{
+        filter::{DisplayFilter, FilterKind},
+        frame::Frame,
+        frontend::{DebugView, Frontend, HostEvent},
+        palette::Palette,
+        recorder::Recorder,
+        screenshot::Screenshot,
+    },
+    logic::{
+        args_service::ArgsService,
+        coverage::Coverage,
+        debugger::{Debugger, DebuggerAction},
+        interpreter::Interpreter,
+        movie::{Movie, MovieMode},
+        profiler::Profiler,
+        rom_watcher::RomWatcher,
+    },
+};
 
-use super::components::{
-    cpu::{Cpu, CpuInst},
-    display::DisplayScreen,
-    keypad::Keypad,
-    memory::Memory,
+use super::{
+    components::{
+        cpu::{Cpu, CpuInst},
+        display::DisplayScreen,
+        keypad::{Keypad, KEY_COUNT},
+        memory::Memory,
+    },
+    save_state::SaveState,
+    speed::{Speed, FRAME_HZ},
 };
 
-pub struct GuestSystem<'a> {
+// Frames run at 60 Hz on every frontend, so the speed no longer depends on how
+// long drawing takes. 11 instructions per frame, about 660 per second, is close
+// to the speed of the old SDL loop that slept 1/160 s in each draw call.
+const CYCLES_PER_FRAME: u32 = 11;
+
+pub struct GuestSystem {
     memory: Memory,
-    display: DisplayScreen<'a>,
+    display: DisplayScreen,
     cpu: Cpu,
-    keypad: Keypad<'a>,
-    sdl_ctx: &'a Sdl,
+    keypad: Keypad,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    debugger: Debugger,
+    stepping: bool,
+    paused: bool,
+    screenshot: Screenshot,
+    recorder: Recorder,
+    filter: DisplayFilter,
+    movie: Option<Movie>,
+    live_keys: u16,
+    movie_keys: u16,
+    rom_path: String,
+    program: Vec<u8>,
+    cycles_per_frame: u32,
+    advance: bool,
+    watcher: Option<RomWatcher>,
+    speed: Speed,
+    frame_count: u64,
+    run_time: Duration,
 }
 
-impl<'a> GuestSystem<'a> {
-    pub fn new(memory: Memory, cpu: Cpu, sdl_ctx: &'a Sdl) -> GuestSystem<'a> {
+impl GuestSystem {
+    pub fn new(memory: Memory, cpu: Cpu) -> GuestSystem {
         GuestSystem {
             memory: memory,
             cpu: cpu,
-            sdl_ctx: sdl_ctx,
-            display: DisplayScreen::new(&sdl_ctx),
-            keypad: Keypad::new(&sdl_ctx),
+            display: DisplayScreen::new(),
+            keypad: Keypad::new(),
+            profiler: None,
+            coverage: None,
+            debugger: Debugger::new(),
+            stepping: false,
+            paused: false,
+            screenshot: Screenshot::new(1, Palette::default()),
+            recorder: Recorder::new(None, 1, Palette::default()),
+            filter: DisplayFilter::new(FilterKind::None),
+            movie: None,
+            live_keys: 0,
+            movie_keys: 0,
+            rom_path: String::new(),
+            program: Vec::new(),
+            cycles_per_frame: CYCLES_PER_FRAME,
+            advance: false,
+            watcher: None,
+            speed: Speed::default(),
+            frame_count: 0,
+            run_time: Duration::ZERO,
         }
     }
 
-    pub fn run_program(&mut self, program: &Vec<u8>, interpreter: &Interpreter) {
-        self.memory.load_fonts(interpreter.generate_fonts());
-        self.memory.load_program(&program);
-        self.cpu.point_pc_to_program();
+    pub fn set_coverage(&mut self, coverage: Coverage) {
+        self.coverage = Some(coverage);
+    }
+
+    pub fn set_screenshot(&mut self, screenshot: Screenshot) {
+        self.screenshot = screenshot;
+    }
+
+    pub fn set_recorder(&mut self, recorder: Recorder) {
+        self.recorder = recorder;
+    }
+
+    pub fn set_filter(&mut self, filter: DisplayFilter) {
+        self.filter = filter;
+    }
 
-        let mut event_pump = self.sdl_ctx.event_pump().unwrap();
+    // Playback runs at the recorded speed, recording stores the current one.
+    pub fn set_movie(&mut self, mut movie: Movie) {
+        match movie.get_mode() {
+            MovieMode::Record => movie.set_cycles_per_frame(self.cycles_per_frame),
+            MovieMode::Play => {
+                if let Some(cycles_per_frame) = movie.get_cycles_per_frame() {
+                    self.cycles_per_frame = cycles_per_frame.max(1);
+                }
+            }
+        }
+        self.movie = Some(movie);
+    }
+
+    // Save states are stored next to this path, and reset reloads the ROM from it.
+    pub fn set_rom_path(&mut self, rom_path: &str) {
+        self.rom_path = rom_path.to_string();
+    }
+
+    pub fn set_watcher(&mut self, watcher: RomWatcher) {
+        self.watcher = Some(watcher);
+    }
+
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+    }
+
+    pub fn get_display(&self) -> &DisplayScreen {
+        &self.display
+    }
+
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    pub fn run_program(
+        &mut self,
+        program: &[u8],
+        interpreter: &Interpreter,
+        frontend: &mut dyn Frontend,
+    ) -> Result<(), EmulatorError> {
+        self.load_program(program, interpreter)?;
+        if self.recorder.has_path() {
+            match self.recorder.start() {
+                Ok(path) => frontend.notify(&format!("Recording to {}", path)),
+                Err(msg) => frontend.notify(&msg),
+            }
+        }
+
+        let started: Instant = Instant::now();
+        let mut next_frame: Instant = started;
         'running: loop {
-            self.cpu.operate_timers();
-            let raw_instruction: u16 = self.cpu.fetch(&self.memory, &interpreter);
-            let instruction: CpuInst = self.cpu.decode(raw_instruction, interpreter);
-            self.cpu.execute(
-                &instruction,
-                &interpreter,
-                &mut self.memory,
-                &mut self.display,
-                &self.keypad,
-                &mut event_pump,
-            );
-            for event in event_pump.poll_iter() {
+            for event in frontend.poll_events() {
                 match event {
-                    Event::KeyDown {
-                        keycode: Some(Keycode::Escape),
-                        ..
+                    HostEvent::Quit => break 'running,
+                    HostEvent::KeyDown(key_val) if self.movie.is_some() => {
+                        self.live_keys |= 1 << key_val
+                    }
+                    HostEvent::KeyUp(key_val) if self.movie.is_some() => {
+                        self.live_keys &= !(1 << key_val)
+                    }
+                    HostEvent::KeyDown(key_val) => self.keypad.set_current_key(key_val),
+                    HostEvent::KeyUp(key_val) => self.keypad.set_released_key(key_val),
+                    HostEvent::Screenshot => match self.screenshot.save_numbered(&self.display) {
+                        Ok(path) => frontend.notify(&format!("Screenshot saved to {}", path)),
+                        Err(msg) => frontend.notify(&msg),
+                    },
+                    HostEvent::ToggleRecording => {
+                        let result: Result<String, String> = if self.recorder.is_recording() {
+                            self.recorder.stop()
+                        } else {
+                            self.recorder
+                                .start()
+                                .map(|path| format!("Recording to {}", path))
+                        };
+                        match result {
+                            Ok(msg) | Err(msg) => frontend.notify(&msg),
+                        }
+                    }
+                    HostEvent::TogglePause => self.paused = !self.paused,
+                    HostEvent::SetTurbo(turbo) => self.speed.set_turbo(turbo),
+                    HostEvent::ToggleSlowMotion => {
+                        let state: &str = if self.speed.toggle_slow_motion() {
+                            "on"
+                        } else {
+                            "off"
+                        };
+                        frontend.notify(&format!(
+                            "Slow motion (1/{}) {}",
+                            self.speed.get_slow_motion_factor(),
+                            state
+                        ));
+                    }
+                    // Runs a single frame and stays paused.
+                    HostEvent::FrameAdvance => {
+                        self.paused = true;
+                        self.advance = true;
                     }
-                    | Event::Quit { .. } => break 'running,
-                    _ => self.handle_keys(&event),
+                    // These would make the run differ from what the movie recorded.
+                    HostEvent::Reset
+                    | HostEvent::LoadRom(_)
+                    | HostEvent::LoadState(_)
+                    | HostEvent::ToggleQuirk(_)
+                    | HostEvent::SetCyclesPerFrame(_)
+                    | HostEvent::Poke(..)
+                        if self.movie.is_some() =>
+                    {
+                        frontend.notify("Not available while a movie is running")
+                    }
+                    HostEvent::Poke(address, byte) if self.paused => {
+                        if let Err(err) = self.memory.set_heap(address, byte) {
+                            frontend.notify(&err.to_string());
+                        }
+                    }
+                    HostEvent::Poke(..) => {}
+                    HostEvent::SetPaused(paused) => self.paused = paused,
+                    HostEvent::SetPalette(palette) => {
+                        self.screenshot.set_palette(palette);
+                        self.recorder.set_palette(palette);
+                    }
+                    HostEvent::ToggleQuirk(quirk) => {
+                        self.cpu.toggle_quirk(quirk);
+                        let state: &str = if self.cpu.get_config().quirk(quirk) {
+                            "on"
+                        } else {
+                            "off"
+                        };
+                        frontend.notify(&format!("{} {}", quirk.label(), state));
+                    }
+                    HostEvent::SetCyclesPerFrame(cycles) => {
+                        self.cycles_per_frame = cycles.max(1);
+                        frontend.notify(&format!(
+                            "Speed {} instructions per frame",
+                            self.cycles_per_frame
+                        ));
+                    }
+                    HostEvent::SaveState(slot) => {
+                        match SaveState::capture(
+                            &self.cpu,
+                            &self.memory,
+                            &self.display,
+                            &self.keypad,
+                        )
+                        .save(&SaveState::slot_path(&self.rom_path, slot))
+                        {
+                            Ok(()) => frontend.notify(&format!("State saved to slot {}", slot)),
+                            Err(msg) => frontend.notify(&msg),
+                        }
+                    }
+                    HostEvent::LoadState(slot) => match self.load_state(slot) {
+                        Ok(()) => frontend.notify(&format!("State loaded from slot {}", slot)),
+                        Err(msg) => frontend.notify(&msg),
+                    },
+                    HostEvent::Reset => {
+                        self.reset(interpreter)?;
+                        frontend.notify("Reset");
+                    }
+                    HostEvent::LoadRom(path) => match self.load_rom(&path, interpreter) {
+                        Ok(()) => {
+                            if let Some(watcher) = self.watcher.as_mut() {
+                                watcher.set_path(&path);
+                            }
+                            frontend.notify(&format!("Loaded {}", path))
+                        }
+                        Err(msg) => frontend.notify(&msg),
+                    },
                 }
             }
+            if let Some(watcher) = self.watcher.as_mut() {
+                if watcher.poll() && self.movie.is_none() {
+                    let path: String = watcher.get_path().to_string();
+                    match self.load_rom(&path, interpreter) {
+                        Ok(()) => frontend.notify(&format!("Reloaded {}", path)),
+                        Err(msg) => frontend.notify(&msg),
+                    }
+                }
+            }
+            let running: bool = !self.paused || mem::take(&mut self.advance);
+            if running {
+                if let Some(movie) = self.movie.as_mut() {
+                    if movie.is_finished() {
+                        frontend.notify("Movie finished");
+                        break 'running;
+                    }
+                    let keys: u16 = movie.next_keys(self.live_keys);
+                    self.apply_movie_keys(keys);
+                }
+                self.cpu.operate_timers();
+                for _ in 0..self.cycles_per_frame {
+                    self.step(interpreter)?;
+                    let break_reason: Option<String> = match self.cpu.take_break_reason() {
+                        Some(reason) => Some(reason),
+                        None if self.stepping => Some("Step".to_string()),
+                        None => None,
+                    };
+                    if let Some(reason) = break_reason {
+                        frontend.suspend();
+                        let action: DebuggerAction =
+                            self.debugger
+                                .break_into(&reason, &self.cpu, &self.memory, interpreter);
+                        frontend.resume();
+                        match action {
+                            DebuggerAction::Continue => self.stepping = false,
+                            DebuggerAction::Step => self.stepping = true,
+                            DebuggerAction::Quit => break 'running,
+                        }
+                        next_frame = Instant::now();
+                    }
+                    if self.cpu.take_vblank_wait() {
+                        break;
+                    }
+                }
+            }
+            let frame: Frame = self.filter.apply(&self.display);
+            frontend.inspect(&DebugView {
+                cpu: &self.cpu,
+                memory: &self.memory,
+                interpreter,
+                paused: self.paused,
+                cycles_per_frame: self.cycles_per_frame,
+                rom_path: &self.rom_path,
+            });
+            frontend.present(&frame);
+            if running {
+                self.frame_count += 1;
+                if let Err(msg) = self
+                    .recorder
+                    .record_frame(&self.display, self.cpu.should_beep())
+                {
+                    frontend.notify(&msg);
+                }
+            }
+            if !frontend.paced() || (self.speed.is_uncapped() && running) {
+                continue;
+            }
+
+            next_frame += Duration::from_secs_f64(1.0 / self.speed.frame_rate());
+            let now: Instant = Instant::now();
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            } else {
+                next_frame = now;
+            }
+        }
+        self.run_time = started.elapsed();
+        Ok(())
+    }
+
+    // Prints the reports collected while the program was running.
+    pub fn finish(&mut self, interpreter: &Interpreter) {
+        if self.recorder.is_recording() {
+            match self.recorder.stop() {
+                Ok(msg) | Err(msg) => println!("{}", msg),
+            }
+        }
+        if let Some(path) = self.screenshot.get_exit_path() {
+            match self.screenshot.save(&self.display, path) {
+                Ok(()) => println!("Screenshot saved to {}", path),
+                Err(msg) => println!("{}", msg),
+            }
+        }
+        if let Some(movie) = &self.movie {
+            if movie.get_mode() == MovieMode::Record {
+                match movie.save() {
+                    Ok(()) => println!("Movie saved to {}", movie.get_path()),
+                    Err(msg) => println!("{}", msg),
+                }
+            }
+        }
+        if self.speed.is_uncapped() {
+            let seconds: f64 = self.run_time.as_secs_f64().max(f64::EPSILON);
+            let frame_rate: f64 = self.frame_count as f64 / seconds;
+            println!(
+                "Ran {} frames in {:.2} s: {:.0} frames per second, {:.1}x real time",
+                self.frame_count,
+                seconds,
+                frame_rate,
+                frame_rate / FRAME_HZ
+            );
+        }
+        self.cpu.print_unknown_opcodes();
+        if let Some(profiler) = &self.profiler {
+            profiler.finish();
+        }
+        if let Some(coverage) = &self.coverage {
+            coverage.finish(&self.memory, interpreter);
         }
     }
 
-    fn handle_keys(&mut self, event: &Event) {
-        if let Event::KeyDown { scancode, .. } = event {
-            self.keypad.set_current_key(*scancode);
+    // Keys are pressed and released in ascending order, so a movie replays
+    // exactly the same keypad events it was recorded with.
+    fn apply_movie_keys(&mut self, keys: u16) {
+        for key_val in 0..KEY_COUNT as u8 {
+            let mask: u16 = 1 << key_val;
+            if (keys ^ self.movie_keys) & mask == 0 {
+                continue;
+            }
+            if keys & mask != 0 {
+                self.keypad.set_current_key(key_val);
+            } else {
+                self.keypad.set_released_key(key_val);
+            }
+        }
+        self.movie_keys = keys;
+    }
+
+    fn load_state(&mut self, slot: u8) -> Result<(), String> {
+        SaveState::load(&SaveState::slot_path(&self.rom_path, slot))?.restore(
+            &mut self.cpu,
+            &mut self.memory,
+            &mut self.display,
+            &mut self.keypad,
+        )
+    }
+
+    // Starts the program over from a clean machine, keeping the configuration.
+    pub fn reset(&mut self, interpreter: &Interpreter) -> Result<(), EmulatorError> {
+        let program: Vec<u8> = self.program.clone();
+        self.cpu.reset();
+        self.memory.clear();
+        self.display.set_hires(false);
+        self.keypad = Keypad::new();
+        self.load_program(&program, interpreter)
+    }
+
+    fn load_rom(&mut self, path: &str, interpreter: &Interpreter) -> Result<(), String> {
+        let (program, _) = ArgsService::new()
+            .read_rom(path)
+            .map_err(|err| err.to_string())?;
+        let previous: Vec<u8> = mem::replace(&mut self.program, program);
+        if let Err(err) = self.reset(interpreter) {
+            self.program = previous;
+            self.reset(interpreter).map_err(|err| err.to_string())?;
+            return Err(err.to_string());
         }
+        self.rom_path = path.to_string();
+        Ok(())
+    }
 
-        if let Event::KeyUp { scancode, .. } = event {
-            self.keypad.set_released_key(*scancode);
+    pub fn load_program(
+        &mut self,
+        program: &[u8],
+        interpreter: &Interpreter,
+    ) -> Result<(), EmulatorError> {
+        self.memory.load_fonts(interpreter.generate_fonts());
+        self.memory.load_program(program)?;
+        self.program = program.to_vec();
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.set_program_length(program.len());
         }
+        self.cpu.point_pc_to_program();
+        Ok(())
+    }
+
+    // Runs a single fetch-decode-execute cycle.
+    pub fn step(&mut self, interpreter: &Interpreter) -> Result<(), EmulatorError> {
+        let pc: u16 = self.cpu.get_pc();
+        let raw_instruction: u16 = self.cpu.fetch(&self.memory, interpreter)?;
+        let instruction: CpuInst = self.cpu.decode(raw_instruction, interpreter);
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(pc, &instruction, &self.memory, interpreter);
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record_fetch(pc);
+            coverage.record_access(
+                &instruction,
+                self.cpu.get_index_register(),
+                self.display.is_hires(),
+            );
+        }
+        self.cpu.execute(
+            &instruction,
+            interpreter,
+            &mut self.memory,
+            &mut self.display,
+            &self.keypad,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::CpuConfig;
+
+    use super::*;
+
+    #[test]
+    fn reset_releases_the_keys() {
+        let mut guest_system: GuestSystem =
+            GuestSystem::new(Memory::with_stack_depth(16), Cpu::new(CpuConfig::default()));
+        let interpreter: Interpreter = Interpreter::new();
+        guest_system
+            .load_program(&[0x12, 0x00], &interpreter)
+            .unwrap();
+        guest_system.keypad.set_current_key(0x5);
+        guest_system.keypad.set_current_key(0xA);
+        guest_system.keypad.set_released_key(0xA);
+        guest_system.reset(&interpreter).unwrap();
+        assert_eq!(guest_system.keypad.get_pressed(), 0);
+        assert_eq!(guest_system.keypad.current_key(), None);
+        assert_eq!(guest_system.keypad.get_released_key(), None);
     }
 }
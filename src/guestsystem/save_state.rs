@@ -0,0 +1,341 @@
+use std::fs;
+
+use crate::config::QUIRKS;
+
+use super::components::{
+    cpu::{Cpu, VARIABLE_REGISTER_COUNT},
+    display::{DisplayScreen, HEIGHT, WIDTH},
+    keypad::Keypad,
+    memory::{Memory, FOUR_KIBI},
+    random::{RandomKind, RandomSource, RANDOM_KINDS},
+};
+
+const MAGIC: &[u8; 4] = b"RC8S";
+const VERSION: u8 = 4;
+const NO_KEY: u8 = 0xFF;
+const PIXEL_BYTES: usize = WIDTH as usize * HEIGHT as usize / 8;
+
+// A snapshot of the machine: registers, timers, stack, memory, screen, keypad,
+// quirks and the random number source.
+//
+//   "RC8S" version
+//   PC I (big endian) V0-VF DT ST
+//   stack length and entries (big endian)
+//   4096 bytes of memory
+//   hires (0 or 1), the 128x64 framebuffer, row by row, 8 pixels per byte
+//   waiting for vblank (0 or 1), quirks (one bit each, in the order of QUIRKS)
+//   held keys (big endian, key 0 in the lowest bit), current and released key
+//   (FF for none)
+//   random kind (index into RANDOM_KINDS), seed and numbers drawn (big endian
+//   64-bit), VIP state (big endian)
+pub struct SaveState {
+    pc: u16,
+    index: u16,
+    registers: [u8; VARIABLE_REGISTER_COUNT],
+    delay_timer: u8,
+    sound_timer: u8,
+    stack: Vec<u16>,
+    heap: Vec<u8>,
+    hires: bool,
+    pixels: Vec<u8>,
+    waiting_for_vblank: bool,
+    quirks: u8,
+    pressed: u16,
+    current_key: Option<u8>,
+    released_key: Option<u8>,
+    random_kind: RandomKind,
+    seed: u64,
+    draws: u64,
+    vip_state: u16,
+}
+
+impl SaveState {
+    pub fn capture(
+        cpu: &Cpu,
+        memory: &Memory,
+        display: &DisplayScreen,
+        keypad: &Keypad,
+    ) -> SaveState {
+        let mut pixels: Vec<u8> = vec![0; PIXEL_BYTES];
+        for (x, column) in display.get_pixels().iter().enumerate() {
+            for (y, lit) in column.iter().enumerate() {
+                if *lit {
+                    let bit: usize = y * WIDTH as usize + x;
+                    pixels[bit / 8] |= 0x80 >> (bit % 8);
+                }
+            }
+        }
+        let quirks: u8 = QUIRKS
+            .iter()
+            .enumerate()
+            .filter(|(_, quirk)| cpu.get_config().quirk(**quirk))
+            .fold(0, |mask, (bit, _)| mask | 1 << bit);
+        let random: &RandomSource = cpu.get_random();
+        SaveState {
+            pc: cpu.get_pc(),
+            index: cpu.get_index_register(),
+            registers: *cpu.get_variable_registers(),
+            delay_timer: cpu.get_delay_timer(),
+            sound_timer: cpu.get_sound_timer(),
+            stack: memory.get_stack().to_vec(),
+            heap: memory.get_heap().to_vec(),
+            hires: display.is_hires(),
+            pixels,
+            waiting_for_vblank: cpu.get_waiting_for_vblank(),
+            quirks,
+            pressed: keypad.get_pressed(),
+            current_key: keypad.current_key(),
+            released_key: keypad.get_released_key(),
+            random_kind: random.get_kind(),
+            seed: random.get_seed(),
+            draws: random.get_draws(),
+            vip_state: random.get_vip_state(),
+        }
+    }
+
+    pub fn restore(
+        &self,
+        cpu: &mut Cpu,
+        memory: &mut Memory,
+        display: &mut DisplayScreen,
+        keypad: &mut Keypad,
+    ) -> Result<(), String> {
+        memory.restore(&self.heap, &self.stack)?;
+        cpu.restore(
+            self.pc,
+            self.index,
+            self.registers,
+            self.delay_timer,
+            self.sound_timer,
+            self.waiting_for_vblank,
+        );
+        for (bit, quirk) in QUIRKS.iter().enumerate() {
+            if cpu.get_config().quirk(*quirk) != (self.quirks & 1 << bit != 0) {
+                cpu.toggle_quirk(*quirk);
+            }
+        }
+        cpu.set_random(RandomSource::resume(
+            self.random_kind,
+            self.seed,
+            self.draws,
+            self.vip_state,
+        ));
+        keypad.restore(self.pressed, self.current_key, self.released_key);
+        display.set_hires(self.hires);
+        for y in 0..HEIGHT as usize {
+            for x in 0..WIDTH as usize {
+                let bit: usize = y * WIDTH as usize + x;
+                display.set_pixel(x, y, self.pixels[bit / 8] & (0x80 >> (bit % 8)) != 0);
+            }
+        }
+        Ok(())
+    }
+
+    // The file of a save slot sits next to the ROM.
+    pub fn slot_path(rom_path: &str, slot: u8) -> String {
+        format!("{}.state{}", rom_path, slot)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut bytes: Vec<u8> = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.pc.to_be_bytes());
+        bytes.extend_from_slice(&self.index.to_be_bytes());
+        bytes.extend_from_slice(&self.registers);
+        bytes.push(self.delay_timer);
+        bytes.push(self.sound_timer);
+        bytes.extend_from_slice(&(self.stack.len() as u16).to_be_bytes());
+        for address in &self.stack {
+            bytes.extend_from_slice(&address.to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.heap);
+        bytes.push(self.hires as u8);
+        bytes.extend_from_slice(&self.pixels);
+        bytes.push(self.waiting_for_vblank as u8);
+        bytes.push(self.quirks);
+        bytes.extend_from_slice(&self.pressed.to_be_bytes());
+        bytes.push(self.current_key.unwrap_or(NO_KEY));
+        bytes.push(self.released_key.unwrap_or(NO_KEY));
+        let kind: usize = RANDOM_KINDS
+            .iter()
+            .position(|name| *name == self.random_kind.name())
+            .unwrap_or(0);
+        bytes.push(kind as u8);
+        bytes.extend_from_slice(&self.seed.to_be_bytes());
+        bytes.extend_from_slice(&self.draws.to_be_bytes());
+        bytes.extend_from_slice(&self.vip_state.to_be_bytes());
+        fs::write(path, bytes).map_err(|err| format!("Cannot save state {}: {}", path, err))
+    }
+
+    pub fn load(path: &str) -> Result<SaveState, String> {
+        let bytes: Vec<u8> =
+            fs::read(path).map_err(|err| format!("Cannot read state {}: {}", path, err))?;
+        let invalid = || format!("{} is not a save state.", path);
+        if !bytes.starts_with(MAGIC) || bytes.get(MAGIC.len()) != Some(&VERSION) {
+            return Err(invalid());
+        }
+        let mut reader: Reader = Reader {
+            bytes: &bytes,
+            offset: MAGIC.len() + 1,
+        };
+        let pc: u16 = reader.word().ok_or_else(invalid)?;
+        let index: u16 = reader.word().ok_or_else(invalid)?;
+        let mut registers: [u8; VARIABLE_REGISTER_COUNT] = [0; VARIABLE_REGISTER_COUNT];
+        registers.copy_from_slice(reader.take(VARIABLE_REGISTER_COUNT).ok_or_else(invalid)?);
+        let delay_timer: u8 = reader.byte().ok_or_else(invalid)?;
+        let sound_timer: u8 = reader.byte().ok_or_else(invalid)?;
+        let stack_length: u16 = reader.word().ok_or_else(invalid)?;
+        let mut stack: Vec<u16> = Vec::new();
+        for _ in 0..stack_length {
+            stack.push(reader.word().ok_or_else(invalid)?);
+        }
+        let heap: Vec<u8> = reader.take(FOUR_KIBI).ok_or_else(invalid)?.to_vec();
+        let hires: bool = reader.byte().ok_or_else(invalid)? != 0;
+        let pixels: Vec<u8> = reader.take(PIXEL_BYTES).ok_or_else(invalid)?.to_vec();
+        let waiting_for_vblank: bool = reader.byte().ok_or_else(invalid)? != 0;
+        let quirks: u8 = reader.byte().ok_or_else(invalid)?;
+        let pressed: u16 = reader.word().ok_or_else(invalid)?;
+        let current_key: Option<u8> = reader.key().ok_or_else(invalid)?;
+        let released_key: Option<u8> = reader.key().ok_or_else(invalid)?;
+        let random_kind: RandomKind = reader
+            .byte()
+            .and_then(|kind| RANDOM_KINDS.get(kind as usize))
+            .and_then(|name| RandomKind::from_name(name))
+            .ok_or_else(invalid)?;
+        let seed: u64 = reader.long().ok_or_else(invalid)?;
+        let draws: u64 = reader.long().ok_or_else(invalid)?;
+        let vip_state: u16 = reader.word().ok_or_else(invalid)?;
+        Ok(SaveState {
+            pc,
+            index,
+            registers,
+            delay_timer,
+            sound_timer,
+            stack,
+            heap,
+            hires,
+            pixels,
+            waiting_for_vblank,
+            quirks,
+            pressed,
+            current_key,
+            released_key,
+            random_kind,
+            seed,
+            draws,
+            vip_state,
+        })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let slice: &'a [u8] = self.bytes.get(self.offset..self.offset + length)?;
+        self.offset += length;
+        Some(slice)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        self.take(1).map(|slice| slice[0])
+    }
+
+    fn word(&mut self) -> Option<u16> {
+        self.take(2)
+            .map(|slice| u16::from_be_bytes([slice[0], slice[1]]))
+    }
+
+    fn long(&mut self) -> Option<u64> {
+        let mut bytes: [u8; 8] = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Some(u64::from_be_bytes(bytes))
+    }
+
+    // A key value, or None for the FF placeholder.
+    fn key(&mut self) -> Option<Option<u8>> {
+        self.byte()
+            .map(|key_val| (key_val != NO_KEY).then_some(key_val))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::config::{CpuConfig, Quirk};
+
+    use super::*;
+
+    #[test]
+    fn round_trips_the_whole_machine() {
+        let mut cpu: Cpu = Cpu::new(CpuConfig::default());
+        let mut memory: Memory = Memory::with_stack_depth(16);
+        let mut display: DisplayScreen = DisplayScreen::new();
+        let mut keypad: Keypad = Keypad::new();
+        memory.load_program(&[0xC0, 0xFF, 0x12, 0x00]).unwrap();
+        memory.push_stack(0x246).unwrap();
+        let mut random: RandomSource = RandomSource::new(RandomKind::Vip, 42);
+        for _ in 0..5 {
+            random.next(&memory);
+        }
+        cpu.set_random(random);
+        cpu.toggle_quirk(Quirk::VfReset);
+        cpu.toggle_quirk(Quirk::ModernShift);
+        cpu.restore(0x202, 0x345, [7; VARIABLE_REGISTER_COUNT], 9, 3, true);
+        display.set_hires(true);
+        display.set_pixel(127, 63, true);
+        keypad.set_current_key(0x5);
+        keypad.set_current_key(0xA);
+        keypad.set_released_key(0xA);
+
+        let path: String = env::temp_dir()
+            .join("rchip8-round-trip.state1")
+            .to_string_lossy()
+            .to_string();
+        SaveState::capture(&cpu, &memory, &display, &keypad)
+            .save(&path)
+            .unwrap();
+        let loaded: SaveState = SaveState::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let mut restored_cpu: Cpu = Cpu::new(CpuConfig::default());
+        let mut restored_memory: Memory = Memory::with_stack_depth(16);
+        let mut restored_display: DisplayScreen = DisplayScreen::new();
+        let mut restored_keypad: Keypad = Keypad::new();
+        loaded
+            .restore(
+                &mut restored_cpu,
+                &mut restored_memory,
+                &mut restored_display,
+                &mut restored_keypad,
+            )
+            .unwrap();
+
+        assert_eq!(restored_cpu.get_pc(), 0x202);
+        assert_eq!(restored_cpu.get_index_register(), 0x345);
+        assert_eq!(restored_cpu.get_delay_timer(), 9);
+        assert!(restored_cpu.get_waiting_for_vblank());
+        for quirk in QUIRKS {
+            assert_eq!(
+                restored_cpu.get_config().quirk(quirk),
+                cpu.get_config().quirk(quirk)
+            );
+        }
+        let random: &RandomSource = restored_cpu.get_random();
+        assert_eq!(random.get_kind(), RandomKind::Vip);
+        assert_eq!(random.get_seed(), 42);
+        assert_eq!(random.get_draws(), 5);
+        assert_eq!(random.get_vip_state(), cpu.get_random().get_vip_state());
+        assert_eq!(restored_memory.get_heap(), memory.get_heap());
+        assert_eq!(restored_memory.get_stack(), memory.get_stack());
+        assert!(restored_display.is_hires());
+        assert!(restored_display.get_pixels()[127][63]);
+        assert_eq!(restored_keypad.get_pressed(), 1 << 0x5);
+        assert_eq!(restored_keypad.current_key(), Some(0x5));
+        assert_eq!(restored_keypad.get_released_key(), Some(0xA));
+    }
+}
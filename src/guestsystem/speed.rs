@@ -0,0 +1,56 @@
+pub const FRAME_HZ: f64 = 60.0;
+pub const TURBO_FACTOR: f64 = 4.0;
+pub const DEFAULT_SLOW_MOTION_FACTOR: f64 = 4.0;
+
+// How fast emulated frames are run compared to real time. Every emulated
+// frame ticks the timers once, so they always keep pace with the program.
+pub struct Speed {
+    slow_motion_factor: f64,
+    slow_motion: bool,
+    turbo: bool,
+    uncapped: bool,
+}
+
+impl Speed {
+    pub fn new(slow_motion_factor: f64, uncapped: bool) -> Speed {
+        Speed {
+            slow_motion_factor,
+            slow_motion: false,
+            turbo: false,
+            uncapped,
+        }
+    }
+
+    pub fn default() -> Speed {
+        Speed::new(DEFAULT_SLOW_MOTION_FACTOR, false)
+    }
+
+    pub fn set_turbo(&mut self, turbo: bool) {
+        self.turbo = turbo;
+    }
+
+    pub fn toggle_slow_motion(&mut self) -> bool {
+        self.slow_motion = !self.slow_motion;
+        self.slow_motion
+    }
+
+    pub fn get_slow_motion_factor(&self) -> f64 {
+        self.slow_motion_factor
+    }
+
+    // Uncapped runs are not paced at all, as a benchmark.
+    pub fn is_uncapped(&self) -> bool {
+        self.uncapped
+    }
+
+    // Emulated frames per second of real time. Turbo wins over slow motion.
+    pub fn frame_rate(&self) -> f64 {
+        if self.turbo {
+            FRAME_HZ * TURBO_FACTOR
+        } else if self.slow_motion {
+            FRAME_HZ / self.slow_motion_factor
+        } else {
+            FRAME_HZ
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::f64::consts::PI;
+
+use crate::guestsystem::components::display::{HEIGHT, WIDTH};
+
+use super::{frame::Frame, palette::Palette};
+
+const DEFAULT_AMOUNT: f64 = 0.5;
+const MAX_CURVATURE: f64 = 0.25;
+
+// Strength of each effect, from 0 (off) to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrtEffects {
+    scanlines: f64,
+    grid: f64,
+    bloom: f64,
+    curvature: f64,
+}
+
+impl CrtEffects {
+    pub fn none() -> CrtEffects {
+        CrtEffects {
+            scanlines: 0.0,
+            grid: 0.0,
+            bloom: 0.0,
+            curvature: 0.0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        *self != CrtEffects::none()
+    }
+
+    // Sets an effect by name, e.g. "scanlines" or "bloom:0.3".
+    pub fn set(&mut self, spec: &str) -> Option<()> {
+        let (name, amount) = match spec.trim().split_once(':') {
+            Some((name, amount)) => (name, amount.parse::<f64>().ok()?),
+            None => (spec.trim(), DEFAULT_AMOUNT),
+        };
+        if !(0.0..=1.0).contains(&amount) {
+            return None;
+        }
+        match name.to_lowercase().as_str() {
+            "scanlines" => self.scanlines = amount,
+            "grid" => self.grid = amount,
+            "bloom" => self.bloom = amount,
+            "curvature" => self.curvature = amount,
+            _ => return None,
+        }
+        Some(())
+    }
+
+    // Renders the frame at the given scale with the effects applied, as RGB.
+    pub fn apply(&self, frame: &Frame, scale: u32, palette: &Palette) -> Vec<u8> {
+        let width: usize = WIDTH as usize * scale as usize;
+        let height: usize = HEIGHT as usize * scale as usize;
+        let base: Vec<f64> = frame
+            .rgb(scale, palette)
+            .iter()
+            .map(|channel| *channel as f64)
+            .collect();
+        let mut image: Vec<f64> = base.clone();
+        if self.scanlines > 0.0 || self.grid > 0.0 {
+            self.apply_mask(&mut image, width, height, scale as usize);
+        }
+        if self.bloom > 0.0 {
+            let glow: Vec<f64> = self.blur(&base, width, height, scale as usize);
+            for (channel, glow) in image.iter_mut().zip(glow) {
+                *channel += glow * self.bloom;
+            }
+        }
+        if self.curvature > 0.0 {
+            image = self.curve(&image, width, height);
+        }
+        image
+            .iter()
+            .map(|channel| channel.round().clamp(0.0, 255.0) as u8)
+            .collect()
+    }
+
+    // Darkens the edges of every pixel row (scanlines) and column (grid).
+    fn apply_mask(&self, image: &mut [f64], width: usize, height: usize, scale: usize) {
+        for y in 0..height {
+            let row: f64 = ((y % scale) as f64 + 0.5) / scale as f64;
+            let mut row_factor: f64 = 1.0 - self.scanlines * (1.0 - (PI * row).sin());
+            if scale > 1 && y % scale == scale - 1 {
+                row_factor *= 1.0 - self.grid;
+            }
+            for x in 0..width {
+                let mut factor: f64 = row_factor;
+                if scale > 1 && x % scale == scale - 1 {
+                    factor *= 1.0 - self.grid;
+                }
+                for channel in &mut image[(y * width + x) * 3..(y * width + x) * 3 + 3] {
+                    *channel *= factor;
+                }
+            }
+        }
+    }
+
+    // A box blur about one CHIP-8 pixel wide, done separately per direction.
+    fn blur(&self, image: &[f64], width: usize, height: usize, radius: usize) -> Vec<f64> {
+        let horizontal: Vec<f64> = box_blur(image, width, height, radius, 3, width * 3);
+        box_blur(&horizontal, height, width, radius, width * 3, 3)
+    }
+
+    // Barrel distortion: every output pixel samples the image further out,
+    // the more so the further it is from the centre.
+    fn curve(&self, image: &[f64], width: usize, height: usize) -> Vec<f64> {
+        let k: f64 = self.curvature * MAX_CURVATURE;
+        let mut curved: Vec<f64> = vec![0.0; image.len()];
+        for y in 0..height {
+            let v: f64 = (y as f64 + 0.5) / height as f64 * 2.0 - 1.0;
+            for x in 0..width {
+                let u: f64 = (x as f64 + 0.5) / width as f64 * 2.0 - 1.0;
+                let source_u: f64 = u * (1.0 + k * v * v);
+                let source_v: f64 = v * (1.0 + k * u * u);
+                if source_u.abs() >= 1.0 || source_v.abs() >= 1.0 {
+                    continue;
+                }
+                let source_x: usize = ((source_u + 1.0) / 2.0 * width as f64) as usize;
+                let source_y: usize = ((source_v + 1.0) / 2.0 * height as f64) as usize;
+                let from: usize = (source_y * width + source_x) * 3;
+                let to: usize = (y * width + x) * 3;
+                curved[to..to + 3].copy_from_slice(&image[from..from + 3]);
+            }
+        }
+        curved
+    }
+}
+
+// Blurs `lines` lines of `length` samples each. `step` is the distance between
+// neighbouring samples and `stride` between lines, so the same code blurs rows
+// and columns.
+fn box_blur(
+    image: &[f64],
+    length: usize,
+    lines: usize,
+    radius: usize,
+    step: usize,
+    stride: usize,
+) -> Vec<f64> {
+    let mut blurred: Vec<f64> = vec![0.0; image.len()];
+    for line in 0..lines {
+        for channel in 0..3 {
+            let at = |i: usize| line * stride + i * step + channel;
+            let mut sum: f64 = 0.0;
+            for i in 0..radius.min(length) {
+                sum += image[at(i)];
+            }
+            for i in 0..length {
+                if i + radius < length {
+                    sum += image[at(i + radius)];
+                }
+                if i > radius {
+                    sum -= image[at(i - radius - 1)];
+                }
+                blurred[at(i)] = sum / (2 * radius + 1) as f64;
+            }
+        }
+    }
+    blurred
+}
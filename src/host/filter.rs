@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+
+use crate::guestsystem::components::display::DisplayScreen;
+
+use super::frame::{Frame, FULL};
+
+const DEFAULT_BLEND_FRAMES: f64 = 3.0;
+const DEFAULT_DECAY: f64 = 0.5;
+const DEFAULT_DEFLICKER_FRAMES: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterKind {
+    None,
+    // Averages the last N frames.
+    Blend(usize),
+    // Lit pixels fade out, keeping this much of their brightness each frame.
+    Phosphor(f64),
+    // Pixels are drawn at once, but only cleared after staying off for N frames.
+    Deflicker(u8),
+}
+
+impl FilterKind {
+    // Parses NAME or NAME:AMOUNT, e.g. "blend:4" or "phosphor:0.7".
+    pub fn from_spec(spec: &str) -> Option<FilterKind> {
+        let (name, amount) = match spec.split_once(':') {
+            Some((name, amount)) => (name, Some(amount.parse::<f64>().ok()?)),
+            None => (spec, None),
+        };
+        match name.to_lowercase().as_str() {
+            "none" => Some(FilterKind::None),
+            "blend" => match amount.unwrap_or(DEFAULT_BLEND_FRAMES) {
+                frames if frames >= 1.0 => Some(FilterKind::Blend(frames as usize)),
+                _ => None,
+            },
+            "phosphor" => match amount.unwrap_or(DEFAULT_DECAY) {
+                decay if (0.0..1.0).contains(&decay) => Some(FilterKind::Phosphor(decay)),
+                _ => None,
+            },
+            "deflicker" => match amount.unwrap_or(DEFAULT_DEFLICKER_FRAMES) {
+                frames if (1.0..255.0).contains(&frames) => {
+                    Some(FilterKind::Deflicker(frames as u8))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+pub struct DisplayFilter {
+    kind: FilterKind,
+    history: VecDeque<Frame>,
+    previous: Option<Frame>,
+    off_frames: Vec<u8>,
+}
+
+impl DisplayFilter {
+    pub fn new(kind: FilterKind) -> DisplayFilter {
+        DisplayFilter {
+            kind,
+            history: VecDeque::new(),
+            previous: None,
+            off_frames: Vec::new(),
+        }
+    }
+
+    // Turns the current framebuffer into the next frame to show.
+    pub fn apply(&mut self, display: &DisplayScreen) -> Frame {
+        let raw: Frame = Frame::from_display(display);
+        match self.kind {
+            FilterKind::None => raw,
+            FilterKind::Blend(frames) => self.blend(raw, frames),
+            FilterKind::Phosphor(decay) => self.phosphor(raw, decay),
+            FilterKind::Deflicker(frames) => self.deflicker(raw, frames),
+        }
+    }
+
+    fn blend(&mut self, raw: Frame, frames: usize) -> Frame {
+        let hires: bool = raw.is_hires();
+        self.history.push_back(raw);
+        while self.history.len() > frames {
+            self.history.pop_front();
+        }
+        let count: u32 = self.history.len() as u32;
+        let length: usize = self.history[0].get_intensities().len();
+        let intensities: Vec<u8> = (0..length)
+            .map(|i| {
+                let sum: u32 = self
+                    .history
+                    .iter()
+                    .map(|frame| frame.get_intensities()[i] as u32)
+                    .sum();
+                (sum / count) as u8
+            })
+            .collect();
+        Frame::new(intensities, hires)
+    }
+
+    fn phosphor(&mut self, raw: Frame, decay: f64) -> Frame {
+        let intensities: Vec<u8> = match &self.previous {
+            Some(previous) => raw
+                .get_intensities()
+                .iter()
+                .zip(previous.get_intensities())
+                .map(|(now, before)| (*now).max((*before as f64 * decay) as u8))
+                .collect(),
+            None => raw.get_intensities().to_vec(),
+        };
+        let frame: Frame = Frame::new(intensities, raw.is_hires());
+        self.previous = Some(frame.clone());
+        frame
+    }
+
+    fn deflicker(&mut self, raw: Frame, frames: u8) -> Frame {
+        let raw_intensities: &[u8] = raw.get_intensities();
+        if self.off_frames.len() != raw_intensities.len() {
+            self.off_frames = vec![u8::MAX; raw_intensities.len()];
+        }
+        let intensities: Vec<u8> = raw_intensities
+            .iter()
+            .zip(self.off_frames.iter_mut())
+            .map(|(intensity, off)| {
+                if *intensity > 0 {
+                    *off = 0;
+                    return *intensity;
+                }
+                *off = off.saturating_add(1);
+                if *off <= frames {
+                    FULL
+                } else {
+                    0
+                }
+            })
+            .collect();
+        Frame::new(intensities, raw.is_hires())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X: usize = 3;
+    const Y: usize = 4;
+
+    // Runs the filter over frames where one pixel is lit or not, and returns
+    // how bright that pixel is shown each time.
+    fn run(kind: FilterKind, lit: &[bool]) -> Vec<u8> {
+        let mut filter: DisplayFilter = DisplayFilter::new(kind);
+        let mut display: DisplayScreen = DisplayScreen::new();
+        lit.iter()
+            .map(|on| {
+                display.set_pixel(X, Y, *on);
+                filter.apply(&display).at(X, Y)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn none_shows_the_framebuffer() {
+        assert_eq!(run(FilterKind::None, &[true, false, true]), [FULL, 0, FULL]);
+    }
+
+    #[test]
+    fn phosphor_decays_lit_pixels() {
+        assert_eq!(
+            run(FilterKind::Phosphor(0.5), &[true, false, false, false]),
+            [FULL, 127, 63, 31]
+        );
+        assert_eq!(
+            run(FilterKind::Phosphor(0.5), &[true, false, true]),
+            [FULL, 127, FULL]
+        );
+    }
+
+    #[test]
+    fn blend_averages_the_last_frames() {
+        assert_eq!(
+            run(FilterKind::Blend(2), &[true, false, false]),
+            [FULL, 127, 0]
+        );
+        assert_eq!(
+            run(FilterKind::Blend(3), &[true, false, true, false]),
+            [FULL, 127, 170, 85]
+        );
+    }
+
+    #[test]
+    fn deflicker_holds_pixels_for_the_given_frames() {
+        assert_eq!(
+            run(FilterKind::Deflicker(2), &[true, false, false, false]),
+            [FULL, FULL, FULL, 0]
+        );
+        assert_eq!(
+            run(FilterKind::Deflicker(1), &[true, false, true, false, false]),
+            [FULL, FULL, FULL, FULL, 0]
+        );
+        assert_eq!(run(FilterKind::Deflicker(2), &[false, false]), [0, 0]);
+    }
+
+    #[test]
+    fn parses_filter_specs() {
+        assert_eq!(FilterKind::from_spec("blend"), Some(FilterKind::Blend(3)));
+        assert_eq!(
+            FilterKind::from_spec("phosphor:0.7"),
+            Some(FilterKind::Phosphor(0.7))
+        );
+        assert_eq!(
+            FilterKind::from_spec("Deflicker:4"),
+            Some(FilterKind::Deflicker(4))
+        );
+        assert_eq!(FilterKind::from_spec("phosphor:1"), None);
+        assert_eq!(FilterKind::from_spec("blend:0"), None);
+        assert_eq!(FilterKind::from_spec("sharpen"), None);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::guestsystem::components::display::{DisplayScreen, HEIGHT, WIDTH};
+
+use super::palette::Palette;
+
+pub const FULL: u8 = 0xFF;
+
+// What is shown on the host: the brightness of every framebuffer pixel, row
+// by row, and whether the program runs in hires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    intensities: Vec<u8>,
+    hires: bool,
+}
+
+impl Frame {
+    pub fn new(intensities: Vec<u8>, hires: bool) -> Frame {
+        Frame { intensities, hires }
+    }
+
+    pub fn from_display(display: &DisplayScreen) -> Frame {
+        let pixels = display.get_pixels();
+        let intensities: Vec<u8> = (0..HEIGHT as usize)
+            .flat_map(|y| {
+                pixels
+                    .iter()
+                    .map(move |column| if column[y] { FULL } else { 0 })
+            })
+            .collect();
+        Frame {
+            intensities,
+            hires: display.is_hires(),
+        }
+    }
+
+    pub fn get_intensities(&self) -> &[u8] {
+        &self.intensities
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    pub fn at(&self, x: usize, y: usize) -> u8 {
+        self.intensities[y * WIDTH as usize + x]
+    }
+
+    pub fn rgb(&self, scale: u32, palette: &Palette) -> Vec<u8> {
+        let mut data: Vec<u8> =
+            Vec::with_capacity(WIDTH as usize * HEIGHT as usize * (scale * scale) as usize * 3);
+        for y in 0..HEIGHT as u32 * scale {
+            for x in 0..WIDTH as u32 * scale {
+                let intensity: u8 = self.at((x / scale) as usize, (y / scale) as usize);
+                data.extend_from_slice(&palette.blend(intensity));
+            }
+        }
+        data
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{
+    config::Quirk,
+    guestsystem::components::{cpu::Cpu, memory::Memory},
+    logic::interpreter::Interpreter,
+};
+
+use super::{frame::Frame, palette::Palette};
+
+// Some events are only sent by the SDL window, its menu and its debug views.
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+pub enum HostEvent {
+    Quit,
+    KeyDown(u8),
+    KeyUp(u8),
+    Screenshot,
+    ToggleRecording,
+    TogglePause,
+    FrameAdvance,
+    SetTurbo(bool),
+    ToggleSlowMotion,
+    Poke(u16, u8),
+    SetPaused(bool),
+    Reset,
+    LoadRom(String),
+    SaveState(u8),
+    LoadState(u8),
+    ToggleQuirk(Quirk),
+    SetCyclesPerFrame(u32),
+    SetPalette(Palette),
+}
+
+// The machine state handed to frontends with debugging views after every frame.
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+pub struct DebugView<'a> {
+    pub cpu: &'a Cpu,
+    pub memory: &'a Memory,
+    pub interpreter: &'a Interpreter,
+    pub paused: bool,
+    pub cycles_per_frame: u32,
+    pub rom_path: &'a str,
+}
+
+// A frontend shows the framebuffer and turns host input into CHIP-8 keys.
+pub trait Frontend {
+    fn poll_events(&mut self) -> Vec<HostEvent>;
+    fn present(&mut self, frame: &Frame);
+
+    // Frontends nobody is watching can run as fast as possible.
+    fn paced(&self) -> bool {
+        true
+    }
+
+    fn inspect(&mut self, _view: &DebugView) {}
+
+    fn notify(&mut self, message: &str) {
+        println!("{}", message);
+    }
+
+    // Hands the terminal back while the debugger reads commands from stdin.
+    fn suspend(&mut self) {}
+
+    fn resume(&mut self) {}
+
+    // The size of the window to restore in the next session, if there is one.
+    fn window_size(&self) -> Option<(u32, u32)> {
+        None
+    }
+}
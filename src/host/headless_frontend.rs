@@ -0,0 +1,52 @@
+use super::{
+    frame::Frame,
+    frontend::{Frontend, HostEvent},
+};
+
+// Runs a fixed number of frames without any window or terminal.
+pub struct HeadlessFrontend {
+    frames: u64,
+    presented: u64,
+    quiet: bool,
+}
+
+impl HeadlessFrontend {
+    pub fn new(frames: u64) -> HeadlessFrontend {
+        HeadlessFrontend {
+            frames,
+            presented: 0,
+            quiet: false,
+        }
+    }
+
+    // Keeps messages like "Movie finished" out of the conformance report.
+    pub fn quiet(frames: u64) -> HeadlessFrontend {
+        HeadlessFrontend {
+            quiet: true,
+            ..HeadlessFrontend::new(frames)
+        }
+    }
+}
+
+impl Frontend for HeadlessFrontend {
+    fn poll_events(&mut self) -> Vec<HostEvent> {
+        if self.presented >= self.frames {
+            return vec![HostEvent::Quit];
+        }
+        Vec::new()
+    }
+
+    fn present(&mut self, _frame: &Frame) {
+        self.presented += 1;
+    }
+
+    fn paced(&self) -> bool {
+        false
+    }
+
+    fn notify(&mut self, message: &str) {
+        if !self.quiet {
+            println!("{}", message);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use std::ops::Range;
+
+use sdl2::{
+    keyboard::Keycode,
+    pixels::PixelFormatEnum,
+    render::{Canvas, Texture, TextureCreator},
+    video::{Window, WindowContext},
+    VideoSubsystem,
+};
+
+use crate::{
+    guestsystem::components::memory::FONTS_ADDRESS,
+    logic::interpreter::{ALL_FONT_COUNT, FONT_SIZE},
+};
+
+use super::{
+    frontend::{DebugView, HostEvent},
+    text::{Rgba, TextCanvas, CELL_HEIGHT, CELL_WIDTH},
+};
+
+const BYTES_PER_ROW: usize = 16;
+const VISIBLE_ROWS: usize = 32;
+const PANEL_LINES: u32 = 5;
+const SPRITE_ROWS: usize = 15;
+const SPRITE_PIXEL: u32 = 3;
+// Columns of the hex bytes and the ASCII text, in characters.
+const HEX_COLUMN: u32 = 5;
+const ASCII_COLUMN: u32 = HEX_COLUMN + BYTES_PER_ROW as u32 * 3 + 1;
+const COLUMNS: u32 = ASCII_COLUMN + BYTES_PER_ROW as u32;
+const MARGIN: u32 = 4;
+const ZOOM: u32 = 2;
+// Written bytes stay highlighted for this many frames, fading out.
+const HEAT_FRAMES: u8 = 60;
+
+const BACKGROUND: Rgba = [16, 16, 24, 255];
+const TEXT_COLOR: Rgba = [200, 200, 200, 255];
+const DIM_COLOR: Rgba = [110, 110, 130, 255];
+const FONT_COLOR: Rgba = [220, 190, 90, 255];
+const WRITE_COLOR: Rgba = [255, 70, 70, 255];
+const PC_COLOR: Rgba = [30, 110, 50, 255];
+const INDEX_COLOR: Rgba = [40, 70, 150, 255];
+const CURSOR_COLOR: Rgba = [230, 230, 230, 255];
+const EDIT_COLOR: Rgba = [230, 140, 40, 255];
+
+// A second window with a live hex dump of the heap. Bytes can be poked at the
+// cursor while the emulator is paused.
+pub struct MemoryViewer {
+    canvas: Canvas<Window>,
+    text: TextCanvas,
+    cursor: u16,
+    top_row: usize,
+    pending_digit: Option<u8>,
+    previous: Vec<u8>,
+    heat: Vec<u8>,
+    paused: bool,
+    pc: u16,
+    index: u16,
+}
+
+impl MemoryViewer {
+    pub fn new(video_subsystem: &VideoSubsystem) -> Result<MemoryViewer, String> {
+        let width: u32 = MARGIN * 2 + COLUMNS * CELL_WIDTH;
+        let height: u32 = MARGIN * 2 + (VISIBLE_ROWS as u32 + 1 + PANEL_LINES) * CELL_HEIGHT;
+        let window: Window = video_subsystem
+            .window("RCHIP-8 - Memory", width * ZOOM, height * ZOOM)
+            .build()
+            .map_err(|err| err.to_string())?;
+        let canvas: Canvas<Window> = window
+            .into_canvas()
+            .build()
+            .map_err(|err| err.to_string())?;
+        Ok(MemoryViewer {
+            canvas,
+            text: TextCanvas::new(width, height),
+            cursor: 0,
+            top_row: 0,
+            pending_digit: None,
+            previous: Vec::new(),
+            heat: Vec::new(),
+            paused: false,
+            pc: 0,
+            index: 0,
+        })
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    pub fn handle_key(&mut self, keycode: Keycode) -> Option<HostEvent> {
+        let target: i32 = match keycode {
+            Keycode::Space => return Some(HostEvent::TogglePause),
+            Keycode::Left => self.cursor as i32 - 1,
+            Keycode::Right => self.cursor as i32 + 1,
+            Keycode::Up => self.cursor as i32 - BYTES_PER_ROW as i32,
+            Keycode::Down => self.cursor as i32 + BYTES_PER_ROW as i32,
+            Keycode::PageUp => self.cursor as i32 - (BYTES_PER_ROW * VISIBLE_ROWS) as i32,
+            Keycode::PageDown => self.cursor as i32 + (BYTES_PER_ROW * VISIBLE_ROWS) as i32,
+            Keycode::Home => self.pc as i32,
+            Keycode::End => self.index as i32,
+            _ => return self.type_digit(keycode),
+        };
+        self.move_cursor(target);
+        None
+    }
+
+    // Two hex digits typed while paused poke a byte and move to the next one.
+    fn type_digit(&mut self, keycode: Keycode) -> Option<HostEvent> {
+        let code: i32 = keycode as i32;
+        if !self.paused || !(0..0x80).contains(&code) {
+            return None;
+        }
+        let digit: u8 = (code as u8 as char).to_digit(16)? as u8;
+        match self.pending_digit.take() {
+            None => {
+                self.pending_digit = Some(digit);
+                None
+            }
+            Some(high) => {
+                let address: u16 = self.cursor;
+                self.move_cursor(address as i32 + 1);
+                Some(HostEvent::Poke(address, high << 4 | digit))
+            }
+        }
+    }
+
+    fn move_cursor(&mut self, target: i32) {
+        let last: i32 = self.previous.len().max(1) as i32 - 1;
+        self.cursor = target.clamp(0, last) as u16;
+        self.pending_digit = None;
+        let row: usize = self.cursor as usize / BYTES_PER_ROW;
+        if row < self.top_row {
+            self.top_row = row;
+        } else if row >= self.top_row + VISIBLE_ROWS {
+            self.top_row = row + 1 - VISIBLE_ROWS;
+        }
+    }
+
+    pub fn present(&mut self, view: &DebugView) {
+        let heap: &[u8] = view.memory.get_heap();
+        self.paused = view.paused;
+        self.pc = view.cpu.get_pc();
+        self.index = view.cpu.get_index_register();
+        if !self.paused {
+            self.pending_digit = None;
+        }
+        self.update_heat(heap);
+
+        self.text.clear(BACKGROUND);
+        for line in 0..VISIBLE_ROWS {
+            self.draw_row(line, heap);
+        }
+        self.draw_panel(view, heap);
+
+        let (width, height) = (self.text.width(), self.text.height());
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "0");
+        let texture_creator: TextureCreator<WindowContext> = self.canvas.texture_creator();
+        let mut texture: Texture = texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGBA32, width, height)
+            .expect("Error while creating the memory viewer texture.");
+        texture
+            .update(None, self.text.get_rgba(), width as usize * 4)
+            .expect("Error while updating the memory viewer texture.");
+        self.canvas.clear();
+        self.canvas
+            .copy(&texture, None, None)
+            .expect("Error while drawing the memory viewer.");
+        self.canvas.present();
+    }
+
+    fn update_heat(&mut self, heap: &[u8]) {
+        if self.previous.len() != heap.len() {
+            self.previous = heap.to_vec();
+            self.heat = vec![0; heap.len()];
+            return;
+        }
+        for (address, byte) in heap.iter().enumerate() {
+            if self.previous[address] != *byte {
+                self.previous[address] = *byte;
+                self.heat[address] = HEAT_FRAMES;
+            } else {
+                self.heat[address] = self.heat[address].saturating_sub(1);
+            }
+        }
+    }
+
+    fn draw_row(&mut self, line: usize, heap: &[u8]) {
+        let address: usize = (self.top_row + line) * BYTES_PER_ROW;
+        if address >= heap.len() {
+            return;
+        }
+        let y: u32 = MARGIN + line as u32 * CELL_HEIGHT;
+        self.text
+            .draw_text(MARGIN, y, &format!("{:03X}", address), DIM_COLOR);
+        for (column, byte) in heap[address..address + BYTES_PER_ROW].iter().enumerate() {
+            let x: u32 = MARGIN + (HEX_COLUMN + column as u32 * 3) * CELL_WIDTH;
+            let (background, color) = self.byte_colors(address + column);
+            if let Some(background) = background {
+                self.text
+                    .fill_rect(x - 1, y - 1, CELL_WIDTH * 2 + 1, CELL_HEIGHT, background);
+            }
+            self.text.draw_text(x, y, &format!("{:02X}", byte), color);
+            let ascii: char = match byte {
+                0x20..=0x7E => *byte as char,
+                _ => '.',
+            };
+            let ascii_x: u32 = MARGIN + (ASCII_COLUMN + column as u32) * CELL_WIDTH;
+            self.text.draw_text(ascii_x, y, &ascii.to_string(), color);
+        }
+    }
+
+    // The background highlight and text colour of a byte.
+    fn byte_colors(&self, address: usize) -> (Option<Rgba>, Rgba) {
+        if address == self.cursor as usize {
+            return match self.pending_digit {
+                Some(_) => (Some(EDIT_COLOR), BACKGROUND),
+                None => (Some(CURSOR_COLOR), BACKGROUND),
+            };
+        }
+        let pc: usize = self.pc as usize;
+        let background: Option<Rgba> = if address == pc || address == pc + 1 {
+            Some(PC_COLOR)
+        } else if address == self.index as usize {
+            Some(INDEX_COLOR)
+        } else {
+            None
+        };
+        let fonts: Range<usize> = FONTS_ADDRESS..FONTS_ADDRESS + FONT_SIZE * ALL_FONT_COUNT;
+        let color: Rgba = if self.heat[address] > 0 {
+            fade(TEXT_COLOR, WRITE_COLOR, self.heat[address])
+        } else if fonts.contains(&address) {
+            FONT_COLOR
+        } else {
+            TEXT_COLOR
+        };
+        (background, color)
+    }
+
+    fn draw_panel(&mut self, view: &DebugView, heap: &[u8]) {
+        let top: u32 = MARGIN + (VISIBLE_ROWS as u32 + 1) * CELL_HEIGHT;
+        let line_y = |line: u32| top + line * CELL_HEIGHT;
+        let status: String = format!(
+            "PC {:03X}  I {:03X}  CURSOR {:03X}  {}",
+            self.pc,
+            self.index,
+            self.cursor,
+            if self.paused { "PAUSED" } else { "RUNNING" }
+        );
+        self.text.draw_text(MARGIN, line_y(0), &status, TEXT_COLOR);
+
+        let disassembly: String = match view.interpreter.fetch(view.memory, self.cursor) {
+            Ok(raw) => format!(
+                "{:03X}  {:04X}  {}",
+                self.cursor,
+                raw,
+                view.interpreter.mnemonic(&view.interpreter.decode(raw))
+            ),
+            Err(_) => format!("{:03X}  -", self.cursor),
+        };
+        self.text
+            .draw_text(MARGIN, line_y(1), &disassembly, TEXT_COLOR);
+
+        let mut x: u32 = MARGIN;
+        for (label, color) in [
+            ("PC", PC_COLOR),
+            ("I", INDEX_COLOR),
+            ("FONT", FONT_COLOR),
+            ("WRITTEN", WRITE_COLOR),
+        ] {
+            self.text
+                .fill_rect(x, line_y(2), CELL_WIDTH, CELL_HEIGHT - 2, color);
+            self.text
+                .draw_text(x + CELL_WIDTH * 2, line_y(2), label, DIM_COLOR);
+            x += (label.len() as u32 + 4) * CELL_WIDTH;
+        }
+        self.text.draw_text(
+            MARGIN,
+            line_y(3),
+            "SPACE PAUSE  ARROWS PGUP PGDN MOVE",
+            DIM_COLOR,
+        );
+        self.text.draw_text(
+            MARGIN,
+            line_y(4),
+            "HOME PC  END I  0-F POKE WHILE PAUSED  ESC CLOSE",
+            DIM_COLOR,
+        );
+        self.draw_sprite(MARGIN + ASCII_COLUMN * CELL_WIDTH, top, heap);
+    }
+
+    // The bytes from the cursor on, drawn as an 8 pixel wide sprite.
+    fn draw_sprite(&mut self, x: u32, y: u32, heap: &[u8]) {
+        self.text.fill_rect(
+            x,
+            y,
+            8 * SPRITE_PIXEL,
+            SPRITE_ROWS as u32 * SPRITE_PIXEL,
+            [0, 0, 0, 255],
+        );
+        let start: usize = self.cursor as usize;
+        let end: usize = (start + SPRITE_ROWS).min(heap.len());
+        for (row, byte) in heap[start..end].iter().enumerate() {
+            for column in 0..8 {
+                if byte & (0x80 >> column) != 0 {
+                    self.text.fill_rect(
+                        x + column * SPRITE_PIXEL,
+                        y + row as u32 * SPRITE_PIXEL,
+                        SPRITE_PIXEL,
+                        SPRITE_PIXEL,
+                        TEXT_COLOR,
+                    );
+                }
+            }
+        }
+        self.text
+            .draw_text(x + 8 * SPRITE_PIXEL + CELL_WIDTH, y, "SPRITE", DIM_COLOR);
+    }
+}
+
+// Blends from one colour towards another by the remaining heat.
+fn fade(from: Rgba, to: Rgba, heat: u8) -> Rgba {
+    let amount: u32 = heat as u32 * 255 / HEAT_FRAMES as u32;
+    let mut color: Rgba = from;
+    for channel in 0..3 {
+        color[channel] =
+            ((from[channel] as u32 * (255 - amount) + to[channel] as u32 * amount) / 255) as u8;
+    }
+    color
+}
@@ -0,0 +1,308 @@
+use std::{fs, path::Path};
+
+use sdl2::keyboard::Keycode;
+
+use crate::config::{CpuConfig, QUIRKS};
+
+use super::{
+    frontend::{DebugView, HostEvent},
+    palette::Palette,
+    text::{Rgba, TextCanvas, CELL_HEIGHT, CELL_WIDTH},
+};
+
+const COLUMNS: u32 = 36;
+const LINES: u32 = 13;
+const FIRST_ITEM_LINE: u32 = 2;
+const VISIBLE_ITEMS: usize = 9;
+const LABEL_WIDTH: usize = 16;
+const MARGIN: u32 = 4;
+const SLOT_COUNT: u8 = 9;
+// Instructions per frame offered by the speed setting.
+const SPEEDS: [u32; 12] = [1, 2, 4, 7, 11, 15, 20, 30, 50, 100, 200, 500];
+const ROM_EXTENSIONS: [&str; 5] = ["ch8", "c8", "sc8", "xo8", "8o"];
+
+const BACKGROUND: Rgba = [0, 0, 0, 210];
+const TEXT_COLOR: Rgba = [230, 230, 230, 255];
+const TITLE_COLOR: Rgba = [255, 210, 60, 255];
+const HINT_COLOR: Rgba = [130, 130, 150, 255];
+const SELECTED_COLOR: Rgba = [60, 80, 140, 255];
+
+const MAIN_ITEMS: [&str; 9] = [
+    "Resume",
+    "Reset",
+    "Load ROM",
+    "Save state",
+    "Load state",
+    "Quirks",
+    "Speed",
+    "Palette",
+    "Quit",
+];
+const LOAD_ROM_ITEM: usize = 2;
+const QUIRKS_ITEM: usize = 5;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Page {
+    Main,
+    Quirks,
+    Roms,
+}
+
+pub enum MenuAction {
+    Close,
+    // Sent to the emulator while the menu stays open.
+    Send(HostEvent),
+    // Sent to the emulator after the menu is closed.
+    Run(HostEvent),
+    Theme(&'static str),
+}
+
+// The pause menu drawn on top of the game. It keeps a copy of the settings it
+// shows, which the emulator refreshes every frame.
+pub struct Menu {
+    text: TextCanvas,
+    page: Page,
+    selected: usize,
+    top: usize,
+    slot: u8,
+    theme: usize,
+    config: Option<CpuConfig>,
+    cycles_per_frame: u32,
+    rom_path: String,
+    roms: Vec<String>,
+}
+
+impl Menu {
+    pub fn new(palette: &Palette) -> Menu {
+        let theme: usize = Palette::theme_names()
+            .iter()
+            .position(|name| Palette::theme(name).as_ref() == Some(palette))
+            .unwrap_or(0);
+        Menu {
+            text: TextCanvas::new(
+                MARGIN * 2 + COLUMNS * CELL_WIDTH,
+                MARGIN * 2 + LINES * CELL_HEIGHT,
+            ),
+            page: Page::Main,
+            selected: 0,
+            top: 0,
+            slot: 1,
+            theme,
+            config: None,
+            cycles_per_frame: 0,
+            rom_path: String::new(),
+            roms: Vec::new(),
+        }
+    }
+
+    pub fn get_canvas(&self) -> &TextCanvas {
+        &self.text
+    }
+
+    pub fn handle_key(&mut self, keycode: Keycode) -> Option<MenuAction> {
+        let count: usize = self.item_count();
+        match keycode {
+            Keycode::Up => self.select((self.selected + count - 1) % count),
+            Keycode::Down => self.select((self.selected + 1) % count),
+            Keycode::Left => return self.adjust(-1),
+            Keycode::Right => return self.adjust(1),
+            Keycode::Return | Keycode::KpEnter | Keycode::Space => return self.activate(),
+            Keycode::Escape | Keycode::Backspace => match self.page {
+                Page::Main => return Some(MenuAction::Close),
+                Page::Quirks => self.open(Page::Main, QUIRKS_ITEM),
+                Page::Roms => self.open(Page::Main, LOAD_ROM_ITEM),
+            },
+            _ => {}
+        }
+        None
+    }
+
+    pub fn update(&mut self, view: &DebugView) {
+        self.config = Some(*view.cpu.get_config());
+        self.cycles_per_frame = view.cycles_per_frame;
+        self.rom_path = view.rom_path.to_string();
+        self.draw();
+    }
+
+    fn item_count(&self) -> usize {
+        match self.page {
+            Page::Main => MAIN_ITEMS.len(),
+            Page::Quirks => QUIRKS.len() + 1,
+            Page::Roms => self.roms.len().max(1),
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index;
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + VISIBLE_ITEMS {
+            self.top = self.selected + 1 - VISIBLE_ITEMS;
+        }
+    }
+
+    fn open(&mut self, page: Page, selected: usize) {
+        self.page = page;
+        self.top = 0;
+        self.select(selected);
+    }
+
+    fn activate(&mut self) -> Option<MenuAction> {
+        match self.page {
+            Page::Main => match MAIN_ITEMS[self.selected] {
+                "Resume" => Some(MenuAction::Close),
+                "Reset" => Some(MenuAction::Run(HostEvent::Reset)),
+                "Load ROM" => {
+                    self.roms = self.find_roms();
+                    self.open(Page::Roms, 0);
+                    None
+                }
+                "Save state" => Some(MenuAction::Run(HostEvent::SaveState(self.slot))),
+                "Load state" => Some(MenuAction::Run(HostEvent::LoadState(self.slot))),
+                "Quirks" => {
+                    self.open(Page::Quirks, 0);
+                    None
+                }
+                "Quit" => Some(MenuAction::Run(HostEvent::Quit)),
+                _ => self.adjust(1),
+            },
+            Page::Quirks => match QUIRKS.get(self.selected) {
+                Some(quirk) => Some(MenuAction::Send(HostEvent::ToggleQuirk(*quirk))),
+                None => {
+                    self.open(Page::Main, QUIRKS_ITEM);
+                    None
+                }
+            },
+            Page::Roms => self
+                .roms
+                .get(self.selected)
+                .map(|path| MenuAction::Run(HostEvent::LoadRom(path.clone()))),
+        }
+    }
+
+    // Left and right step through the values of the slot, speed and palette items.
+    fn adjust(&mut self, step: i32) -> Option<MenuAction> {
+        if self.page != Page::Main {
+            return None;
+        }
+        match MAIN_ITEMS[self.selected] {
+            "Save state" | "Load state" => {
+                self.slot = ((self.slot as i32 - 1 + step).rem_euclid(SLOT_COUNT as i32) + 1) as u8;
+                None
+            }
+            "Speed" => {
+                let current: u32 = self.cycles_per_frame;
+                let cycles: Option<u32> = if step > 0 {
+                    SPEEDS.iter().copied().find(|speed| *speed > current)
+                } else {
+                    SPEEDS.iter().copied().rev().find(|speed| *speed < current)
+                };
+                cycles.map(|cycles| MenuAction::Send(HostEvent::SetCyclesPerFrame(cycles)))
+            }
+            "Palette" => {
+                let names: Vec<&'static str> = Palette::theme_names();
+                self.theme = (self.theme as i32 + step).rem_euclid(names.len() as i32) as usize;
+                Some(MenuAction::Theme(names[self.theme]))
+            }
+            _ => None,
+        }
+    }
+
+    // The ROMs in the directory of the running one, sorted by name.
+    fn find_roms(&self) -> Vec<String> {
+        let directory: &Path = match Path::new(&self.rom_path).parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        let mut roms: Vec<String> = match fs::read_dir(directory) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.extension()
+                        .and_then(|extension| extension.to_str())
+                        .is_some_and(|extension| {
+                            ROM_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+                        })
+                })
+                .map(|path| path.to_string_lossy().to_string())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        roms.sort();
+        roms
+    }
+
+    fn draw(&mut self) {
+        self.text.clear(BACKGROUND);
+        let title: &str = match self.page {
+            Page::Main => "PAUSED",
+            Page::Quirks => "QUIRKS",
+            Page::Roms => "LOAD ROM",
+        };
+        self.draw_line(0, title, TITLE_COLOR);
+        let items: Vec<String> = self.item_labels();
+        for (index, item) in items.iter().enumerate().skip(self.top).take(VISIBLE_ITEMS) {
+            let line: u32 = FIRST_ITEM_LINE + (index - self.top) as u32;
+            if index == self.selected {
+                self.text.fill_rect(
+                    MARGIN - 2,
+                    MARGIN + line * CELL_HEIGHT - 1,
+                    COLUMNS * CELL_WIDTH + 4,
+                    CELL_HEIGHT,
+                    SELECTED_COLOR,
+                );
+            }
+            self.draw_line(line, item, TEXT_COLOR);
+        }
+        self.draw_line(LINES - 1, "Arrows move  Enter select  Esc back", HINT_COLOR);
+    }
+
+    fn item_labels(&self) -> Vec<String> {
+        match self.page {
+            Page::Main => MAIN_ITEMS
+                .iter()
+                .map(|item| {
+                    let value: String = match *item {
+                        "Save state" | "Load state" => format!("< {} >", self.slot),
+                        "Speed" => format!("< {} IPF >", self.cycles_per_frame),
+                        "Palette" => format!("< {} >", Palette::theme_names()[self.theme]),
+                        _ => String::new(),
+                    };
+                    format!("{:<width$}{}", item, value, width = LABEL_WIDTH)
+                })
+                .collect(),
+            Page::Quirks => {
+                let mut labels: Vec<String> = QUIRKS
+                    .iter()
+                    .map(|quirk| {
+                        let on: bool = self.config.is_some_and(|config| config.quirk(*quirk));
+                        format!("[{}] {}", if on { "X" } else { " " }, quirk.label())
+                    })
+                    .collect();
+                labels.push("Back".to_string());
+                labels
+            }
+            Page::Roms if self.roms.is_empty() => vec!["No ROMs found".to_string()],
+            Page::Roms => self
+                .roms
+                .iter()
+                .map(|path| {
+                    let name: String = Path::new(path)
+                        .file_name()
+                        .map_or(path.clone(), |name| name.to_string_lossy().to_string());
+                    name.chars().take(COLUMNS as usize).collect()
+                })
+                .collect(),
+        }
+    }
+
+    // Upper case, so names with an x don't get the small x of hex numbers.
+    fn draw_line(&mut self, line: u32, text: &str, color: Rgba) {
+        self.text.draw_text(
+            MARGIN,
+            MARGIN + line * CELL_HEIGHT,
+            &text.to_uppercase(),
+            color,
+        );
+    }
+}
@@ -0,0 +1,77 @@
+pub const PALETTE_SIZE: usize = 2;
+
+// Background and foreground. The XO-CHIP plane colours would join them once
+// its second plane is emulated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    colors: [[u8; 3]; PALETTE_SIZE],
+}
+
+const THEMES: &[(&str, [u32; PALETTE_SIZE])] = &[
+    ("mono", [0x000000, 0xFFFFFF]),
+    ("green", [0x0C160C, 0x33FF66]),
+    ("amber", [0x140C00, 0xFFB000]),
+    ("lcd", [0x9BBC0F, 0x0F380F]),
+    ("octo", [0x996600, 0xFFCC00]),
+];
+
+impl Palette {
+    pub fn new(colors: [[u8; 3]; PALETTE_SIZE]) -> Palette {
+        Palette { colors }
+    }
+
+    pub fn default() -> Palette {
+        Palette::theme("mono").expect("The mono theme is missing.")
+    }
+
+    pub fn theme(name: &str) -> Option<Palette> {
+        let (_, colors) = THEMES
+            .iter()
+            .find(|(theme, _)| theme.eq_ignore_ascii_case(name))?;
+        Some(Palette::new(colors.map(|color| {
+            [(color >> 16) as u8, (color >> 8) as u8, color as u8]
+        })))
+    }
+
+    pub fn theme_names() -> Vec<&'static str> {
+        THEMES.iter().map(|(name, _)| *name).collect()
+    }
+
+    // Parses "#RRGGBB" or "RRGGBB".
+    pub fn parse_color(hex: &str) -> Option<[u8; 3]> {
+        let hex: &str = hex.trim().trim_start_matches('#');
+        if hex.len() != 6 {
+            return None;
+        }
+        let value: u32 = u32::from_str_radix(hex, 16).ok()?;
+        Some([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+    }
+
+    pub fn set_color(&mut self, index: usize, color: [u8; 3]) {
+        self.colors[index] = color;
+    }
+
+    pub fn colors(&self) -> &[[u8; 3]; PALETTE_SIZE] {
+        &self.colors
+    }
+
+    pub fn background(&self) -> [u8; 3] {
+        self.colors[0]
+    }
+
+    pub fn foreground(&self) -> [u8; 3] {
+        self.colors[1]
+    }
+
+    // Mixes the background and foreground, from 0 (off) to 255 (fully lit).
+    pub fn blend(&self, intensity: u8) -> [u8; 3] {
+        let [background, foreground] = [self.background(), self.foreground()];
+        let mut color: [u8; 3] = [0; 3];
+        for i in 0..3 {
+            let mixed: u32 = background[i] as u32 * (255 - intensity as u32)
+                + foreground[i] as u32 * intensity as u32;
+            color[i] = (mixed / 255) as u8;
+        }
+        color
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use crate::guestsystem::components::display::{DisplayScreen, HEIGHT, WIDTH};
+
+use super::{
+    frame::Frame,
+    palette::Palette,
+    screenshot::numbered_path,
+    wav::{WavWriter, SAMPLE_RATE},
+};
+
+const FRAME_HZ: u32 = 60;
+const BEEP_HZ: u32 = 440;
+const BEEP_AMPLITUDE: i16 = 0x2000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordFormat {
+    Gif,
+    Raw,
+}
+
+impl RecordFormat {
+    // GIF for .gif files, raw RGB frames for anything else.
+    pub fn from_path(path: &str) -> RecordFormat {
+        if path.to_lowercase().ends_with(".gif") {
+            RecordFormat::Gif
+        } else {
+            RecordFormat::Raw
+        }
+    }
+}
+
+struct GifSink {
+    encoder: gif::Encoder<BufWriter<File>>,
+    width: u16,
+    height: u16,
+    // The global palette of the file. Frames in other colours carry their own.
+    palette: Palette,
+    pending: Option<(Vec<u8>, Palette)>,
+    pending_frames: u64,
+    written_frames: u64,
+}
+
+struct RawSink {
+    video: BufWriter<File>,
+    audio: WavWriter,
+    audio_path: String,
+    samples: u64,
+}
+
+enum Sink {
+    Gif(GifSink),
+    Raw(RawSink),
+}
+
+pub struct Recorder {
+    path: Option<String>,
+    scale: u32,
+    palette: Palette,
+    sink: Option<(String, Sink)>,
+}
+
+impl Recorder {
+    pub fn new(path: Option<String>, scale: u32, palette: Palette) -> Recorder {
+        Recorder {
+            path,
+            scale,
+            palette,
+            sink: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    pub fn has_path(&self) -> bool {
+        self.path.is_some()
+    }
+
+    // Applies to the frames recorded from now on.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    // Returns the path of the new recording.
+    pub fn start(&mut self) -> Result<String, String> {
+        let path: String = match &self.path {
+            Some(path) => path.clone(),
+            None => numbered_path("gif")?,
+        };
+        let sink: Sink = match RecordFormat::from_path(&path) {
+            RecordFormat::Gif => Sink::Gif(self.open_gif(&path)?),
+            RecordFormat::Raw => Sink::Raw(self.open_raw(&path)?),
+        };
+        self.sink = Some((path.clone(), sink));
+        Ok(path)
+    }
+
+    // Returns a message describing the finished recording.
+    pub fn stop(&mut self) -> Result<String, String> {
+        let (path, sink) = match self.sink.take() {
+            Some(sink) => sink,
+            None => return Err("Not recording.".to_string()),
+        };
+        let error = |err: &dyn Display| format!("Cannot finish {}: {}", path, err);
+        match sink {
+            Sink::Gif(mut gif) => {
+                Recorder::write_pending(&mut gif).map_err(|err| error(&err))?;
+                gif.encoder
+                    .into_inner()
+                    .and_then(|mut output| output.flush())
+                    .map_err(|err| error(&err))?;
+                Ok(format!("Recording saved to {}", path))
+            }
+            Sink::Raw(mut raw) => {
+                raw.video.flush().map_err(|err| error(&err))?;
+                raw.audio.finish().map_err(|err| error(&err))?;
+                Ok(format!(
+                    "Recording saved to {} and {}, mux with: ffmpeg -f rawvideo -pixel_format rgb24 -video_size {}x{} -framerate {} -i {} -i {} out.mp4",
+                    path,
+                    raw.audio_path,
+                    WIDTH as u32 * self.scale,
+                    HEIGHT as u32 * self.scale,
+                    FRAME_HZ,
+                    path,
+                    raw.audio_path
+                ))
+            }
+        }
+    }
+
+    pub fn record_frame(&mut self, display: &DisplayScreen, beeping: bool) -> Result<(), String> {
+        let scale: u32 = self.scale;
+        let palette: Palette = self.palette;
+        let result = match &mut self.sink {
+            Some((_, Sink::Gif(gif))) => {
+                let frame: (Vec<u8>, Palette) = (Recorder::gif_indices(display, scale), palette);
+                if gif.pending.as_ref() == Some(&frame) {
+                    gif.pending_frames += 1;
+                    return Ok(());
+                }
+                let result = Recorder::write_pending(gif);
+                gif.pending = Some(frame);
+                gif.pending_frames = 1;
+                result.map_err(|err| err.to_string())
+            }
+            Some((_, Sink::Raw(raw))) => {
+                Recorder::write_raw(raw, display, scale, &palette, beeping)
+                    .map_err(|err| err.to_string())
+            }
+            None => return Ok(()),
+        };
+        if let Err(err) = result {
+            let path: String = self.sink.take().map(|(path, _)| path).unwrap_or_default();
+            return Err(format!("Recording to {} stopped: {}", path, err));
+        }
+        Ok(())
+    }
+
+    fn open_gif(&self, path: &str) -> Result<GifSink, String> {
+        // GIF sizes are 16 bit.
+        let (width, height): (u16, u16) = match (
+            u16::try_from(WIDTH as u32 * self.scale),
+            u16::try_from(HEIGHT as u32 * self.scale),
+        ) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => {
+                return Err(format!(
+                    "Cannot record {}: scale {} is too large for a GIF.",
+                    path, self.scale
+                ))
+            }
+        };
+        let file: File = File::create(path)
+            .map_err(|err| format!("Cannot create recording {}: {}", path, err))?;
+        let global_palette: Vec<u8> = self.palette.colors().concat();
+        let mut encoder = gif::Encoder::new(BufWriter::new(file), width, height, &global_palette)
+            .map_err(|err| format!("Cannot write recording {}: {}", path, err))?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(|err| format!("Cannot write recording {}: {}", path, err))?;
+        Ok(GifSink {
+            encoder,
+            width,
+            height,
+            palette: self.palette,
+            pending: None,
+            pending_frames: 0,
+            written_frames: 0,
+        })
+    }
+
+    fn open_raw(&self, path: &str) -> Result<RawSink, String> {
+        let video: File = File::create(path)
+            .map_err(|err| format!("Cannot create recording {}: {}", path, err))?;
+        let audio_path: String = match path.rfind('.') {
+            Some(dot) => format!("{}.wav", &path[..dot]),
+            None => format!("{}.wav", path),
+        };
+        let audio: WavWriter = WavWriter::create(&audio_path)
+            .map_err(|err| format!("Cannot create recording {}: {}", audio_path, err))?;
+        Ok(RawSink {
+            video: BufWriter::new(video),
+            audio,
+            audio_path,
+            samples: 0,
+        })
+    }
+
+    fn gif_indices(display: &DisplayScreen, scale: u32) -> Vec<u8> {
+        let pixels = display.get_pixels();
+        let mut indices: Vec<u8> = Vec::new();
+        for y in 0..HEIGHT as u32 * scale {
+            for x in 0..WIDTH as u32 * scale {
+                indices.push(pixels[(x / scale) as usize][(y / scale) as usize] as u8);
+            }
+        }
+        indices
+    }
+
+    // Identical frames are merged into one GIF frame with a longer delay.
+    // Delays are in hundredths of a second, so they are rounded against the
+    // running total to keep 60 fps on average.
+    fn write_pending(gif: &mut GifSink) -> Result<(), gif::EncodingError> {
+        let (indices, palette) = match gif.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let start: u64 = gif.written_frames * 100 / FRAME_HZ as u64;
+        gif.written_frames += gif.pending_frames;
+        let end: u64 = gif.written_frames * 100 / FRAME_HZ as u64;
+        let frame = gif::Frame {
+            width: gif.width,
+            height: gif.height,
+            delay: (end - start).clamp(1, u16::MAX as u64) as u16,
+            buffer: Cow::Owned(indices),
+            palette: (palette != gif.palette).then(|| palette.colors().concat()),
+            ..gif::Frame::default()
+        };
+        gif.encoder.write_frame(&frame)
+    }
+
+    fn write_raw(
+        raw: &mut RawSink,
+        display: &DisplayScreen,
+        scale: u32,
+        palette: &Palette,
+        beeping: bool,
+    ) -> io::Result<()> {
+        raw.video
+            .write_all(&Frame::from_display(display).rgb(scale, palette))?;
+        let samples_per_frame: u64 = (SAMPLE_RATE / FRAME_HZ) as u64;
+        let half_period: u64 = (SAMPLE_RATE / BEEP_HZ / 2) as u64;
+        let samples: Vec<i16> = (raw.samples..raw.samples + samples_per_frame)
+            .map(|sample| {
+                // Even half periods are high. A bit test, as is_multiple_of needs Rust 1.87.
+                let high: bool = (sample / half_period) & 1 == 0;
+                match (beeping, high) {
+                    (false, _) => 0,
+                    (true, true) => BEEP_AMPLITUDE,
+                    (true, false) => -BEEP_AMPLITUDE,
+                }
+            })
+            .collect();
+        raw.samples += samples_per_frame;
+        raw.audio.write_samples(&samples)
+    }
+}
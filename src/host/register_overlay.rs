@@ -0,0 +1,128 @@
+use crate::guestsystem::components::{
+    cpu::{Cpu, VARIABLE_REGISTER_COUNT},
+    memory::Memory,
+};
+
+use super::{
+    frontend::DebugView,
+    text::{Rgba, TextCanvas, CELL_HEIGHT, CELL_WIDTH},
+};
+
+const COLUMNS: u32 = 28;
+const LINES: u32 = 11;
+const STACK_PER_LINE: usize = 4;
+const STACK_LINES: usize = 2;
+const MARGIN: u32 = 3;
+
+const BACKGROUND: Rgba = [0, 0, 0, 170];
+const TEXT_COLOR: Rgba = [230, 230, 230, 255];
+const LABEL_COLOR: Rgba = [130, 130, 150, 255];
+const CHANGED_COLOR: Rgba = [255, 210, 60, 255];
+
+// V0-VF, I, PC, the timers, the call stack and the next instruction, drawn on
+// top of the game. Registers that changed since the last frame stand out.
+pub struct RegisterOverlay {
+    text: TextCanvas,
+    previous: [u8; VARIABLE_REGISTER_COUNT],
+}
+
+impl RegisterOverlay {
+    pub fn new() -> RegisterOverlay {
+        RegisterOverlay {
+            text: TextCanvas::new(
+                MARGIN * 2 + COLUMNS * CELL_WIDTH,
+                MARGIN * 2 + LINES * CELL_HEIGHT,
+            ),
+            previous: [0; VARIABLE_REGISTER_COUNT],
+        }
+    }
+
+    pub fn get_canvas(&self) -> &TextCanvas {
+        &self.text
+    }
+
+    pub fn update(&mut self, view: &DebugView) {
+        let cpu: &Cpu = view.cpu;
+        let memory: &Memory = view.memory;
+        self.text.clear(BACKGROUND);
+        self.draw_line(
+            0,
+            &format!(
+                "PC {:03X}  I {:03X}  SP {}/{}",
+                cpu.get_pc(),
+                cpu.get_index_register(),
+                memory.get_stack_pointer(),
+                memory.get_stack_depth()
+            ),
+            TEXT_COLOR,
+        );
+        self.draw_line(
+            1,
+            &format!(
+                "DT {:02X}  ST {:02X}",
+                cpu.get_delay_timer(),
+                cpu.get_sound_timer()
+            ),
+            TEXT_COLOR,
+        );
+
+        let registers: [u8; VARIABLE_REGISTER_COUNT] = *cpu.get_variable_registers();
+        for (index, value) in registers.iter().enumerate() {
+            let x: u32 = MARGIN + (index as u32 % 4) * 7 * CELL_WIDTH;
+            let y: u32 = MARGIN + (3 + index as u32 / 4) * CELL_HEIGHT;
+            let color: Rgba = if *value != self.previous[index] {
+                CHANGED_COLOR
+            } else {
+                TEXT_COLOR
+            };
+            self.text
+                .draw_text(x, y, &format!("V{:X}", index), LABEL_COLOR);
+            self.text
+                .draw_text(x + 3 * CELL_WIDTH, y, &format!("{:02X}", value), color);
+        }
+        self.previous = registers;
+
+        self.draw_line(7, "STACK", LABEL_COLOR);
+        // Newest first. When the stack does not fit, the last entry says how
+        // many older ones are left out.
+        let stack: &[u16] = memory.get_stack();
+        let room: usize = STACK_PER_LINE * STACK_LINES;
+        let shown: usize = if stack.len() > room {
+            room - 1
+        } else {
+            stack.len()
+        };
+        let mut entries: Vec<String> = stack
+            .iter()
+            .rev()
+            .take(shown)
+            .map(|address| format!("{:03X}", address))
+            .collect();
+        if stack.len() > shown {
+            entries.push(format!("+{} MORE", stack.len() - shown));
+        }
+        for (line, addresses) in entries.chunks(STACK_PER_LINE).enumerate() {
+            self.text.draw_text(
+                MARGIN + 6 * CELL_WIDTH,
+                MARGIN + (7 + line as u32) * CELL_HEIGHT,
+                &addresses.join(" "),
+                TEXT_COLOR,
+            );
+        }
+
+        let next: String = match view.interpreter.fetch(memory, cpu.get_pc()) {
+            Ok(raw) => format!(
+                "{:04X}  {}",
+                raw,
+                view.interpreter.mnemonic(&view.interpreter.decode(raw))
+            ),
+            Err(_) => "-".to_string(),
+        };
+        self.draw_line(10, &next, TEXT_COLOR);
+    }
+
+    fn draw_line(&mut self, line: u32, text: &str, color: Rgba) {
+        self.text
+            .draw_text(MARGIN, MARGIN + line * CELL_HEIGHT, text, color);
+    }
+}
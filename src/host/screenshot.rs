@@ -0,0 +1,71 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use crate::guestsystem::components::display::{DisplayScreen, HEIGHT, WIDTH};
+
+use super::{frame::Frame, palette::Palette};
+
+const NUMBERED_PREFIX: &str = "rchip8-";
+
+pub struct Screenshot {
+    scale: u32,
+    palette: Palette,
+    exit_path: Option<String>,
+}
+
+impl Screenshot {
+    pub fn new(scale: u32, palette: Palette) -> Screenshot {
+        Screenshot {
+            scale,
+            palette,
+            exit_path: None,
+        }
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    pub fn set_exit_path(&mut self, path: String) {
+        self.exit_path = Some(path);
+    }
+
+    pub fn get_exit_path(&self) -> Option<&str> {
+        self.exit_path.as_deref()
+    }
+
+    pub fn save_numbered(&self, display: &DisplayScreen) -> Result<String, String> {
+        let path: String = numbered_path("png")?;
+        self.save(display, &path)?;
+        Ok(path)
+    }
+
+    pub fn save(&self, display: &DisplayScreen, path: &str) -> Result<(), String> {
+        let width: u32 = WIDTH as u32 * self.scale;
+        let height: u32 = HEIGHT as u32 * self.scale;
+        let file: File = match File::create(path) {
+            Ok(file) => file,
+            Err(err) => return Err(format!("Cannot create screenshot {}: {}", path, err)),
+        };
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| {
+                writer
+                    .write_image_data(&Frame::from_display(display).rgb(self.scale, &self.palette))
+            })
+            .map_err(|err| format!("Cannot write screenshot {}: {}", path, err))
+    }
+}
+
+// The first free rchip8-NNN.<extension> in the working directory.
+pub fn numbered_path(extension: &str) -> Result<String, String> {
+    (1..=u32::MAX)
+        .map(|n: u32| format!("{}{:03}.{}", NUMBERED_PREFIX, n, extension))
+        .find(|path| !Path::new(path).exists())
+        .ok_or(format!(
+            "No free {}NNN.{} file name left.",
+            NUMBERED_PREFIX, extension
+        ))
+}
@@ -0,0 +1,464 @@
+use std::time::{Duration, Instant};
+
+use sdl2::{
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Scancode},
+    pixels::{Color, PixelFormatEnum},
+    rect::Rect,
+    render::{BlendMode, Canvas, Texture, TextureCreator},
+    video::{FullscreenType, Window, WindowContext},
+    EventPump, Sdl, VideoSubsystem,
+};
+
+use crate::guestsystem::components::display::{HEIGHT, WIDTH};
+
+use super::{
+    crt::CrtEffects,
+    frame::Frame,
+    frontend::{DebugView, Frontend, HostEvent},
+    memory_viewer::MemoryViewer,
+    menu::{Menu, MenuAction},
+    palette::Palette,
+    register_overlay::RegisterOverlay,
+    text::{Rgba, TextCanvas, CELL_HEIGHT, CELL_WIDTH},
+    window::{Scaling, WindowConfig},
+};
+
+const LETTERBOX_COLOR: Color = Color::BLACK;
+// CRT effects are rendered at most at this scale and stretched from there.
+const MAX_CRT_SCALE: u32 = 12;
+// Overlays are scaled up by one step for every this many pixels of screen height.
+const OVERLAY_SCALE_STEP: u32 = 160;
+const OSD_DURATION: Duration = Duration::from_secs(2);
+const OSD_MARGIN: u32 = 3;
+const OSD_BACKGROUND: Rgba = [0, 0, 0, 170];
+const OSD_COLOR: Rgba = [255, 255, 255, 255];
+
+pub struct SdlFrontend {
+    canvas: Canvas<Window>,
+    video_subsystem: VideoSubsystem,
+    event_pump: EventPump,
+    palette: Palette,
+    window_config: WindowConfig,
+    crt: CrtEffects,
+    fullscreen: bool,
+    windowed_size: (u32, u32),
+    memory_viewer: Option<MemoryViewer>,
+    register_overlay: Option<RegisterOverlay>,
+    menu: Option<Menu>,
+    paused: bool,
+    paused_before_menu: bool,
+    osd: Option<(TextCanvas, Instant)>,
+}
+
+impl SdlFrontend {
+    pub fn new(
+        palette: Palette,
+        title: Option<&str>,
+        window_config: WindowConfig,
+        crt: CrtEffects,
+        memory_viewer: bool,
+    ) -> Result<SdlFrontend, String> {
+        let context: Sdl = sdl2::init()?;
+        let video_subsystem: VideoSubsystem = context.video()?;
+        let window: Window = video_subsystem
+            .window(
+                &match title {
+                    Some(title) => format!("RCHIP-8 - {}", title),
+                    None => "RCHIP-8".to_string(),
+                },
+                window_config.width(),
+                window_config.height(),
+            )
+            .position_centered()
+            .resizable()
+            .build()
+            .map_err(|err| err.to_string())?;
+        let mut canvas: Canvas<Window> = window
+            .into_canvas()
+            .build()
+            .map_err(|err| err.to_string())?;
+        if window_config.fullscreen() {
+            canvas
+                .window_mut()
+                .set_fullscreen(FullscreenType::Desktop)?;
+        }
+        let event_pump: EventPump = context.event_pump()?;
+        let memory_viewer: Option<MemoryViewer> = if memory_viewer {
+            Some(MemoryViewer::new(&video_subsystem)?)
+        } else {
+            None
+        };
+        Ok(SdlFrontend {
+            canvas,
+            video_subsystem,
+            event_pump,
+            palette,
+            window_config,
+            crt,
+            fullscreen: window_config.fullscreen(),
+            windowed_size: (window_config.width(), window_config.height()),
+            memory_viewer,
+            register_overlay: None,
+            menu: None,
+            paused: false,
+            paused_before_menu: false,
+            osd: None,
+        })
+    }
+
+    fn toggle_memory_viewer(&mut self) {
+        if self.memory_viewer.take().is_none() {
+            match MemoryViewer::new(&self.video_subsystem) {
+                Ok(viewer) => self.memory_viewer = Some(viewer),
+                Err(err) => println!("Cannot open the memory viewer: {}", err),
+            }
+        }
+    }
+
+    // The game is paused while the menu is open, and resumes afterwards
+    // unless it was paused before.
+    fn open_menu(&mut self, events: &mut Vec<HostEvent>) {
+        self.menu = Some(Menu::new(&self.palette));
+        self.paused_before_menu = self.paused;
+        events.push(HostEvent::SetPaused(true));
+    }
+
+    fn close_menu(&mut self, events: &mut Vec<HostEvent>) {
+        self.menu = None;
+        events.push(HostEvent::SetPaused(self.paused_before_menu));
+    }
+
+    fn run_menu_action(&mut self, action: MenuAction, events: &mut Vec<HostEvent>) {
+        match action {
+            MenuAction::Close => self.close_menu(events),
+            MenuAction::Send(event) => events.push(event),
+            MenuAction::Run(event) => {
+                self.close_menu(events);
+                events.push(event);
+            }
+            MenuAction::Theme(name) => {
+                if let Some(palette) = Palette::theme(name) {
+                    self.palette = palette;
+                    events.push(HostEvent::SetPalette(palette));
+                    self.notify(&format!("Palette {}", name));
+                }
+            }
+        }
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        let fullscreen_type: FullscreenType = if self.fullscreen {
+            FullscreenType::Off
+        } else {
+            FullscreenType::Desktop
+        };
+        if self
+            .canvas
+            .window_mut()
+            .set_fullscreen(fullscreen_type)
+            .is_ok()
+        {
+            self.fullscreen = !self.fullscreen;
+        }
+    }
+
+    //  QWERTY  EMULATED
+    // 1 2 3 4 | 1 2 3 C
+    // Q W E R | 4 5 6 D
+    // A S D F | 7 8 9 E
+    // Z X C V | A 0 B F
+    fn scancode_to_byte(&self, scancode: &Scancode) -> Option<u8> {
+        match scancode {
+            Scancode::Num1 => Some(0x1),
+            Scancode::Num2 => Some(0x2),
+            Scancode::Num3 => Some(0x3),
+            Scancode::Num4 => Some(0xC),
+            Scancode::Q => Some(0x4),
+            Scancode::W => Some(0x5),
+            Scancode::E => Some(0x6),
+            Scancode::R => Some(0xD),
+            Scancode::A => Some(0x7),
+            Scancode::S => Some(0x8),
+            Scancode::D => Some(0x9),
+            Scancode::F => Some(0xE),
+            Scancode::Z => Some(0xA),
+            Scancode::X => Some(0x0),
+            Scancode::C => Some(0xB),
+            Scancode::V => Some(0xF),
+            _ => None,
+        }
+    }
+}
+
+impl Frontend for SdlFrontend {
+    fn poll_events(&mut self) -> Vec<HostEvent> {
+        let mut events: Vec<HostEvent> = Vec::new();
+        let sdl_events: Vec<Event> = self.event_pump.poll_iter().collect();
+        let main_id: u32 = self.canvas.window().id();
+        for event in sdl_events {
+            let event_window: Option<u32> = event.get_window_id();
+            if let Some(viewer) = self
+                .memory_viewer
+                .as_mut()
+                .filter(|viewer| event_window == Some(viewer.window_id()))
+            {
+                match event {
+                    Event::Window {
+                        win_event: WindowEvent::Close,
+                        ..
+                    }
+                    | Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => self.memory_viewer = None,
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        ..
+                    } => events.extend(viewer.handle_key(keycode)),
+                    _ => {}
+                }
+                continue;
+            }
+            if self.menu.is_some() && event.get_window_id() == Some(main_id) {
+                match event {
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F1),
+                        repeat: false,
+                        ..
+                    } => {
+                        self.close_menu(&mut events);
+                        continue;
+                    }
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        ..
+                    } => {
+                        let action: Option<MenuAction> =
+                            self.menu.as_mut().and_then(|menu| menu.handle_key(keycode));
+                        if let Some(action) = action {
+                            self.run_menu_action(action, &mut events);
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            match event {
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                }
+                | Event::Quit { .. } => events.push(HostEvent::Quit),
+                Event::Window {
+                    window_id,
+                    win_event: WindowEvent::Close,
+                    ..
+                } if window_id == main_id => events.push(HostEvent::Quit),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F1),
+                    repeat: false,
+                    ..
+                } => self.open_menu(&mut events),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    repeat: false,
+                    ..
+                } => self.toggle_memory_viewer(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F3),
+                    repeat: false,
+                    ..
+                } => {
+                    self.register_overlay = match self.register_overlay {
+                        Some(_) => None,
+                        None => Some(RegisterOverlay::new()),
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => events.push(HostEvent::Screenshot),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    repeat: false,
+                    ..
+                } => self.toggle_fullscreen(),
+                Event::Window {
+                    win_event: WindowEvent::Resized(width, height),
+                    ..
+                } if !self.fullscreen => self.windowed_size = (width as u32, height as u32),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5 | Keycode::Pause),
+                    repeat: false,
+                    ..
+                } => events.push(HostEvent::TogglePause),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F6),
+                    ..
+                } => events.push(HostEvent::FrameAdvance),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F7),
+                    repeat: false,
+                    ..
+                } => events.push(HostEvent::Reset),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F8),
+                    repeat: false,
+                    ..
+                } => events.push(HostEvent::ToggleSlowMotion),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Tab),
+                    repeat: false,
+                    ..
+                } => events.push(HostEvent::SetTurbo(true)),
+                Event::KeyUp {
+                    keycode: Some(Keycode::Tab),
+                    ..
+                } => events.push(HostEvent::SetTurbo(false)),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    repeat: false,
+                    ..
+                } => events.push(HostEvent::ToggleRecording),
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(key_val) = self.scancode_to_byte(&scancode) {
+                        events.push(HostEvent::KeyDown(key_val));
+                    }
+                }
+                Event::KeyUp {
+                    scancode: Some(scancode),
+                    ..
+                } => {
+                    if let Some(key_val) = self.scancode_to_byte(&scancode) {
+                        events.push(HostEvent::KeyUp(key_val));
+                    }
+                }
+                _ => {}
+            }
+        }
+        events
+    }
+
+    fn present(&mut self, frame: &Frame) {
+        let (output_width, output_height) = self
+            .canvas
+            .output_size()
+            .expect("Error while reading the window size.");
+        let (x, y, width, height) = self.window_config.letterbox(output_width, output_height);
+
+        // Without CRT effects the texture has one texel per CHIP-8 pixel and
+        // the GPU scales it, with them the effects are rendered at the window scale.
+        let (scale, rgb) = if self.crt.is_enabled() {
+            let scale: u32 = (width / WIDTH as u32).clamp(1, MAX_CRT_SCALE);
+            (scale, self.crt.apply(frame, scale, &self.palette))
+        } else {
+            (1, frame.rgb(1, &self.palette))
+        };
+        let exact: bool = width == WIDTH as u32 * scale;
+        let quality: &str = match self.window_config.scaling() {
+            Scaling::Integer if exact || scale == 1 => "0",
+            _ => "1",
+        };
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", quality);
+        let texture_creator: TextureCreator<WindowContext> = self.canvas.texture_creator();
+        let mut texture: Texture = texture_creator
+            .create_texture_streaming(
+                PixelFormatEnum::RGB24,
+                WIDTH as u32 * scale,
+                HEIGHT as u32 * scale,
+            )
+            .expect("Error while creating the screen texture.");
+        texture
+            .update(None, &rgb, WIDTH as usize * scale as usize * 3)
+            .expect("Error while updating the screen texture.");
+
+        self.canvas.set_draw_color(LETTERBOX_COLOR);
+        self.canvas.clear();
+        self.canvas
+            .copy(&texture, None, Rect::new(x, y, width, height))
+            .expect("Error while drawing the screen.");
+        let overlay_scale: u32 = (height / OVERLAY_SCALE_STEP).max(1);
+        if let Some(overlay) = &self.register_overlay {
+            draw_canvas(&mut self.canvas, overlay.get_canvas(), x, y, overlay_scale);
+        }
+        if let Some(menu) = &self.menu {
+            let text: &TextCanvas = menu.get_canvas();
+            let menu_x: i32 = x + (width as i32 - (text.width() * overlay_scale) as i32) / 2;
+            let menu_y: i32 = y + (height as i32 - (text.height() * overlay_scale) as i32) / 2;
+            draw_canvas(&mut self.canvas, text, menu_x, menu_y, overlay_scale);
+        }
+        if let Some((text, shown)) = &self.osd {
+            if shown.elapsed() < OSD_DURATION {
+                let margin: u32 = OSD_MARGIN * overlay_scale;
+                let osd_y: i32 =
+                    y + height as i32 - (text.height() * overlay_scale + margin) as i32;
+                draw_canvas(
+                    &mut self.canvas,
+                    text,
+                    x + margin as i32,
+                    osd_y,
+                    overlay_scale,
+                );
+            } else {
+                self.osd = None;
+            }
+        }
+        self.canvas.present();
+    }
+
+    fn inspect(&mut self, view: &DebugView) {
+        self.paused = view.paused;
+        if let Some(menu) = self.menu.as_mut() {
+            menu.update(view);
+        }
+        if let Some(overlay) = self.register_overlay.as_mut() {
+            overlay.update(view);
+        }
+        if let Some(viewer) = self.memory_viewer.as_mut() {
+            viewer.present(view);
+        }
+    }
+
+    // Messages are printed and shown for a moment at the bottom of the screen.
+    fn notify(&mut self, message: &str) {
+        println!("{}", message);
+        let mut text: TextCanvas = TextCanvas::new(
+            OSD_MARGIN * 2 + message.chars().count() as u32 * CELL_WIDTH,
+            OSD_MARGIN * 2 + CELL_HEIGHT - 2,
+        );
+        text.clear(OSD_BACKGROUND);
+        text.draw_text(OSD_MARGIN, OSD_MARGIN, &message.to_uppercase(), OSD_COLOR);
+        self.osd = Some((text, Instant::now()));
+    }
+
+    fn window_size(&self) -> Option<(u32, u32)> {
+        Some(self.windowed_size)
+    }
+}
+
+// Draws an RGBA canvas over the screen with alpha blending, scale screen
+// pixels per canvas pixel.
+fn draw_canvas(canvas: &mut Canvas<Window>, text: &TextCanvas, x: i32, y: i32, scale: u32) {
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "0");
+    let texture_creator: TextureCreator<WindowContext> = canvas.texture_creator();
+    let mut texture: Texture = texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGBA32, text.width(), text.height())
+        .expect("Error while creating the overlay texture.");
+    texture.set_blend_mode(BlendMode::Blend);
+    texture
+        .update(None, text.get_rgba(), text.width() as usize * 4)
+        .expect("Error while updating the overlay texture.");
+    canvas
+        .copy(
+            &texture,
+            None,
+            Rect::new(x, y, text.width() * scale, text.height() * scale),
+        )
+        .expect("Error while drawing the overlay.");
+}
@@ -0,0 +1,96 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+pub const DEFAULT_SETTINGS_PATH: &str = "rchip8.cfg";
+
+// Settings are stored as "key = value" lines; lines starting with # are comments.
+pub struct Settings {
+    path: String,
+    values: BTreeMap<String, String>,
+    persistent: bool,
+}
+
+impl Settings {
+    // A file that is not there yet is only created when it was asked for.
+    pub fn load(path: &str, create: bool) -> Result<Settings, String> {
+        let persistent: bool = create || Path::new(path).exists();
+        let mut values: BTreeMap<String, String> = BTreeMap::new();
+        let text: String =
+            read_text(path).map_err(|err| format!("Cannot read {}: {}", path, err))?;
+        for (number, line) in text.lines().enumerate() {
+            match Settings::parse_line(line) {
+                Some(Some((key, value))) => {
+                    values.insert(key, value);
+                }
+                Some(None) => {}
+                None => return Err(format!("{}: line {} is not key = value.", path, number + 1)),
+            }
+        }
+        Ok(Settings {
+            path: path.to_string(),
+            values,
+            persistent,
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|value| value.as_str())
+    }
+
+    // Whether changes should be saved to the file.
+    pub fn is_persistent(&self) -> bool {
+        self.persistent
+    }
+
+    pub fn set(&mut self, key: &str, value: String) {
+        self.values.insert(key.to_string(), value);
+    }
+
+    // Rewrites the lines of changed keys in place, so comments and order survive.
+    pub fn save(&self) -> Result<(), String> {
+        let text: String =
+            read_text(&self.path).map_err(|err| format!("Cannot read {}: {}", self.path, err))?;
+        let mut written: Vec<&str> = Vec::new();
+        let mut lines: Vec<String> = Vec::new();
+        for line in text.lines() {
+            match Settings::parse_line(line) {
+                Some(Some((key, _))) if self.values.contains_key(&key) => {
+                    let (key, value) = self.values.get_key_value(&key).unwrap();
+                    lines.push(format!("{} = {}", key, value));
+                    written.push(key);
+                }
+                _ => lines.push(line.to_string()),
+            }
+        }
+        for (key, value) in &self.values {
+            if !written.contains(&key.as_str()) {
+                lines.push(format!("{} = {}", key, value));
+            }
+        }
+        let mut text: String = lines.join("\n");
+        text.push('\n');
+        fs::write(&self.path, text).map_err(|err| format!("Cannot save {}: {}", self.path, err))
+    }
+
+    // None for malformed lines, Some(None) for blank lines and comments.
+    fn parse_line(line: &str) -> Option<Option<(String, String)>> {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Some(None);
+        }
+        let (key, value) = line.split_once('=')?;
+        Some(Some((key.trim().to_lowercase(), value.trim().to_string())))
+    }
+}
+
+// A missing file reads as empty, any other error is passed on.
+fn read_text(path: &str) -> io::Result<String> {
+    match fs::read_to_string(path) {
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(String::new()),
+        result => result,
+    }
+}
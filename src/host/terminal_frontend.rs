@@ -0,0 +1,348 @@
+use std::{
+    io::{self, stdout, Stdout, Write},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor,
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute, queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+
+use crate::guestsystem::components::{
+    display::{HEIGHT, LORES_HEIGHT, WIDTH},
+    keypad::KEY_COUNT,
+};
+
+use super::{
+    frame::{Frame, FULL},
+    frontend::{Frontend, HostEvent},
+    palette::Palette,
+};
+
+const BRAILLE_BASE: u32 = 0x2800;
+// Braille cells have no shades, so dimmer pixels are left out.
+const BRAILLE_THRESHOLD: u8 = FULL / 2 + 1;
+// Dot bits of a braille cell, indexed by [column][row].
+const BRAILLE_DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+// Most terminals only report key presses, so keys are let go after this long.
+// It has to outlast the usual key repeat delay of 250 to 500 ms, or a held key
+// would drop out before the first repeat arrives.
+const KEY_HOLD: Duration = Duration::from_millis(600);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TerminalGlyphs {
+    HalfBlocks,
+    Braille,
+}
+
+pub struct TerminalFrontend {
+    out: Stdout,
+    glyphs: TerminalGlyphs,
+    palette: Palette,
+    key_releases: bool,
+    held_keys: [Option<Instant>; KEY_COUNT],
+    turbo_held: Option<Instant>,
+    last_frame: Option<Vec<String>>,
+}
+
+impl TerminalFrontend {
+    pub fn new(glyphs: TerminalGlyphs, palette: Palette) -> io::Result<TerminalFrontend> {
+        let mut frontend: TerminalFrontend = TerminalFrontend {
+            out: stdout(),
+            glyphs,
+            palette,
+            key_releases: false,
+            held_keys: [None; KEY_COUNT],
+            turbo_held: None,
+            last_frame: None,
+        };
+        frontend.enter()?;
+        Ok(frontend)
+    }
+
+    // Switches to raw mode on the alternate screen.
+    fn enter(&mut self) -> io::Result<()> {
+        terminal::enable_raw_mode()?;
+        if let Err(err) = self.set_up_screen() {
+            self.leave();
+            return Err(err);
+        }
+        self.last_frame = None;
+        Ok(())
+    }
+
+    fn set_up_screen(&mut self) -> io::Result<()> {
+        execute!(
+            self.out,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            terminal::Clear(terminal::ClearType::All)
+        )?;
+        self.key_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if self.key_releases {
+            execute!(
+                self.out,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        Ok(())
+    }
+
+    // Restores the terminal the way it was before enter.
+    fn leave(&mut self) {
+        if self.key_releases {
+            let _ = execute!(self.out, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.out, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+
+    //  QWERTY  EMULATED
+    // 1 2 3 4 | 1 2 3 C
+    // Q W E R | 4 5 6 D
+    // A S D F | 7 8 9 E
+    // Z X C V | A 0 B F
+    fn char_to_byte(&self, c: char) -> Option<u8> {
+        match c.to_ascii_lowercase() {
+            '1' => Some(0x1),
+            '2' => Some(0x2),
+            '3' => Some(0x3),
+            '4' => Some(0xC),
+            'q' => Some(0x4),
+            'w' => Some(0x5),
+            'e' => Some(0x6),
+            'r' => Some(0xD),
+            'a' => Some(0x7),
+            's' => Some(0x8),
+            'd' => Some(0x9),
+            'f' => Some(0xE),
+            'z' => Some(0xA),
+            'x' => Some(0x0),
+            'c' => Some(0xB),
+            'v' => Some(0xF),
+            _ => None,
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, events: &mut Vec<HostEvent>) {
+        let ctrl_c: bool =
+            key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        if key.code == KeyCode::Esc || ctrl_c {
+            events.push(HostEvent::Quit);
+            return;
+        }
+        if key.code == KeyCode::Tab {
+            if key.kind == KeyEventKind::Release {
+                self.turbo_held = None;
+                events.push(HostEvent::SetTurbo(false));
+            } else {
+                if self.turbo_held.is_none() {
+                    events.push(HostEvent::SetTurbo(true));
+                }
+                self.turbo_held = Some(Instant::now());
+            }
+            return;
+        }
+        if let KeyCode::F(n @ (5 | 6 | 7 | 8 | 9 | 12)) = key.code {
+            if key.kind == KeyEventKind::Press || (n == 6 && key.kind == KeyEventKind::Repeat) {
+                events.push(match n {
+                    5 => HostEvent::TogglePause,
+                    6 => HostEvent::FrameAdvance,
+                    7 => HostEvent::Reset,
+                    8 => HostEvent::ToggleSlowMotion,
+                    9 => HostEvent::ToggleRecording,
+                    _ => HostEvent::Screenshot,
+                });
+            }
+            return;
+        }
+        let key_val: u8 = match key.code {
+            KeyCode::Char(c) => match self.char_to_byte(c) {
+                Some(key_val) => key_val,
+                None => return,
+            },
+            _ => return,
+        };
+        match key.kind {
+            KeyEventKind::Press | KeyEventKind::Repeat => {
+                if self.held_keys[key_val as usize].is_none() {
+                    events.push(HostEvent::KeyDown(key_val));
+                }
+                self.held_keys[key_val as usize] = Some(Instant::now());
+            }
+            KeyEventKind::Release => {
+                self.held_keys[key_val as usize] = None;
+                events.push(HostEvent::KeyUp(key_val));
+            }
+        }
+    }
+
+    // The lines below the screen, which is as tall as the last frame drawn.
+    fn rows(&self) -> u16 {
+        match &self.last_frame {
+            Some(lines) => lines.len() as u16,
+            None => match self.glyphs {
+                TerminalGlyphs::HalfBlocks => LORES_HEIGHT as u16 / 2,
+                TerminalGlyphs::Braille => LORES_HEIGHT as u16 / 4,
+            },
+        }
+    }
+
+    // Lores frames are shown a screen pixel per framebuffer block, so they
+    // take no more room than on the 64x32 screen.
+    fn screen_pixels(frame: &Frame) -> (usize, usize, usize) {
+        let block: usize = if frame.is_hires() { 1 } else { 2 };
+        (WIDTH as usize / block, HEIGHT as usize / block, block)
+    }
+
+    // Every cell shows two pixels: the upper half in the foreground colour and
+    // the lower half in the background colour, so filtered frames keep their shades.
+    fn render_half_blocks(&self, frame: &Frame) -> Vec<String> {
+        let (width, height, block) = TerminalFrontend::screen_pixels(frame);
+        let at = |x: usize, y: usize| frame.at(x * block, y * block);
+        (0..height)
+            .step_by(2)
+            .map(|y| {
+                let mut line: String = String::new();
+                let mut colors: Option<([u8; 3], [u8; 3])> = None;
+                for x in 0..width {
+                    let top: [u8; 3] = self.palette.blend(at(x, y));
+                    let bottom: [u8; 3] = self.palette.blend(at(x, y + 1));
+                    if colors != Some((top, bottom)) {
+                        let [r, g, b] = top;
+                        line.push_str(&SetForegroundColor(Color::Rgb { r, g, b }).to_string());
+                        let [r, g, b] = bottom;
+                        line.push_str(&SetBackgroundColor(Color::Rgb { r, g, b }).to_string());
+                        colors = Some((top, bottom));
+                    }
+                    line.push('▀');
+                }
+                line
+            })
+            .collect()
+    }
+
+    fn render_braille(&self, frame: &Frame) -> Vec<String> {
+        let (width, height, block) = TerminalFrontend::screen_pixels(frame);
+        let at = |x: usize, y: usize| frame.at(x * block, y * block);
+        (0..height)
+            .step_by(4)
+            .map(|y| {
+                (0..width)
+                    .step_by(2)
+                    .map(|x| {
+                        let mut dots: u32 = 0;
+                        for (dx, column) in BRAILLE_DOTS.iter().enumerate() {
+                            for (dy, dot) in column.iter().enumerate() {
+                                if at(x + dx, y + dy) >= BRAILLE_THRESHOLD {
+                                    dots |= dot;
+                                }
+                            }
+                        }
+                        char::from_u32(BRAILLE_BASE + dots).unwrap_or(' ')
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn draw(&mut self, lines: &[String]) -> io::Result<()> {
+        let [r, g, b] = self.palette.foreground();
+        queue!(self.out, SetForegroundColor(Color::Rgb { r, g, b }))?;
+        let [r, g, b] = self.palette.background();
+        queue!(self.out, SetBackgroundColor(Color::Rgb { r, g, b }))?;
+        for (row, line) in lines.iter().enumerate() {
+            queue!(self.out, cursor::MoveTo(0, row as u16), Print(line))?;
+        }
+        queue!(self.out, ResetColor)?;
+        self.out.flush()
+    }
+}
+
+impl Frontend for TerminalFrontend {
+    fn poll_events(&mut self) -> Vec<HostEvent> {
+        let mut events: Vec<HostEvent> = Vec::new();
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            match event::read() {
+                Ok(Event::Key(key)) => self.handle_key(key, &mut events),
+                Ok(Event::Resize(_, _)) => {
+                    self.last_frame = None;
+                    let _ = execute!(self.out, terminal::Clear(terminal::ClearType::All));
+                }
+                _ => {}
+            }
+        }
+        if !self.key_releases {
+            if let Some(pressed_at) = self.turbo_held {
+                if pressed_at.elapsed() >= KEY_HOLD {
+                    self.turbo_held = None;
+                    events.push(HostEvent::SetTurbo(false));
+                }
+            }
+            for key_val in 0..KEY_COUNT {
+                if let Some(pressed_at) = self.held_keys[key_val] {
+                    if pressed_at.elapsed() >= KEY_HOLD {
+                        self.held_keys[key_val] = None;
+                        events.push(HostEvent::KeyUp(key_val as u8));
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    fn present(&mut self, frame: &Frame) {
+        let lines: Vec<String> = match self.glyphs {
+            TerminalGlyphs::HalfBlocks => self.render_half_blocks(frame),
+            TerminalGlyphs::Braille => self.render_braille(frame),
+        };
+        if self.last_frame.as_ref() == Some(&lines) {
+            return;
+        }
+        // Switching between lores and hires changes the size of the screen.
+        if self
+            .last_frame
+            .as_ref()
+            .is_some_and(|last| last.len() != lines.len())
+        {
+            let _ = execute!(self.out, terminal::Clear(terminal::ClearType::All));
+        }
+        // A failed draw is tried again with the next frame.
+        if self.draw(&lines).is_ok() {
+            self.last_frame = Some(lines);
+        }
+    }
+
+    // Messages go on the line below the screen, so they don't break the frame.
+    fn notify(&mut self, message: &str) {
+        let row: u16 = self.rows();
+        let _ = execute!(
+            self.out,
+            cursor::MoveTo(0, row),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            Print(message)
+        );
+    }
+
+    fn suspend(&mut self) {
+        self.leave();
+    }
+
+    fn resume(&mut self) {
+        if let Err(err) = self.enter() {
+            eprintln!("Cannot switch the terminal back to raw mode: {}", err);
+        }
+    }
+}
+
+impl Drop for TerminalFrontend {
+    fn drop(&mut self) {
+        self.leave();
+    }
+}
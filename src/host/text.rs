@@ -0,0 +1,148 @@
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+pub const CELL_WIDTH: u32 = GLYPH_WIDTH + 1;
+pub const CELL_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+
+pub type Rgba = [u8; 4];
+
+const FIRST_GLYPH: u8 = b' ';
+// 5x7 glyphs for ' ' to '_', one row per byte with the leftmost pixel in bit 4.
+const GLYPHS: [[u8; GLYPH_HEIGHT as usize]; 64] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // !
+    [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00], // "
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // #
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // $
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // %
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // &
+    [0x04, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00], // '
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // (
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // )
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // *
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ,
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // .
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // /
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // 0
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // 1
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // 2
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // 3
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // 4
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // 5
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // 6
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // 8
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // :
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ;
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // <
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // =
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // >
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // ?
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E], // @
+    [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11], // A
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // B
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // C
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // D
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // E
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // F
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // G
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // H
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // I
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // L
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // O
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // P
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // Q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // R
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // S
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // W
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // X
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // Y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // Z
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // [
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // \
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ]
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // _
+];
+// Lower case letters use the capitals, except for the x of hex numbers.
+const SMALL_X: [u8; GLYPH_HEIGHT as usize] = [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11];
+const BAR: [u8; GLYPH_HEIGHT as usize] = [0x04; GLYPH_HEIGHT as usize];
+
+// An RGBA pixel buffer with a built-in bitmap font, for the debugging
+// windows and overlays drawn by the frontends.
+pub struct TextCanvas {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+impl TextCanvas {
+    pub fn new(width: u32, height: u32) -> TextCanvas {
+        TextCanvas {
+            width,
+            height,
+            rgba: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get_rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    pub fn clear(&mut self, color: Rgba) {
+        for pixel in self.rgba.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Rgba) {
+        for row in y..(y + height).min(self.height) {
+            for column in x..(x + width).min(self.width) {
+                let offset: usize = (row as usize * self.width as usize + column as usize) * 4;
+                self.rgba[offset..offset + 4].copy_from_slice(&color);
+            }
+        }
+    }
+
+    // Draws text with its top left corner at pixel (x, y), one cell per character.
+    pub fn draw_text(&mut self, x: u32, y: u32, text: &str, color: Rgba) {
+        for (index, character) in text.chars().enumerate() {
+            let glyph: &[u8; GLYPH_HEIGHT as usize] = glyph(character);
+            let left: u32 = x + index as u32 * CELL_WIDTH;
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (0x10 >> column) != 0 {
+                        self.fill_rect(left + column, y + row as u32, 1, 1, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn glyph(character: char) -> &'static [u8; GLYPH_HEIGHT as usize] {
+    match character {
+        'x' => &SMALL_X,
+        '|' => &BAR,
+        ' '..='_' => &GLYPHS[(character as u8 - FIRST_GLYPH) as usize],
+        'a'..='z' => &GLYPHS[(character.to_ascii_uppercase() as u8 - FIRST_GLYPH) as usize],
+        _ => &GLYPHS[(b'?' - FIRST_GLYPH) as usize],
+    }
+}
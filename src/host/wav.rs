@@ -0,0 +1,52 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+};
+
+pub const SAMPLE_RATE: u32 = 44100;
+const HEADER_SIZE: u32 = 44;
+
+// 16-bit mono PCM. The sizes in the header are filled in by finish().
+pub struct WavWriter {
+    output: BufWriter<File>,
+    data_size: u32,
+}
+
+impl WavWriter {
+    pub fn create(path: &str) -> io::Result<WavWriter> {
+        let mut output: BufWriter<File> = BufWriter::new(File::create(path)?);
+        output.write_all(b"RIFF")?;
+        output.write_all(&0u32.to_le_bytes())?;
+        output.write_all(b"WAVEfmt ")?;
+        output.write_all(&16u32.to_le_bytes())?;
+        output.write_all(&1u16.to_le_bytes())?;
+        output.write_all(&1u16.to_le_bytes())?;
+        output.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        output.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+        output.write_all(&2u16.to_le_bytes())?;
+        output.write_all(&16u16.to_le_bytes())?;
+        output.write_all(b"data")?;
+        output.write_all(&0u32.to_le_bytes())?;
+        Ok(WavWriter {
+            output,
+            data_size: 0,
+        })
+    }
+
+    pub fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        for sample in samples {
+            self.output.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_size += samples.len() as u32 * 2;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.output.seek(SeekFrom::Start(4))?;
+        self.output
+            .write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.output.seek(SeekFrom::Start(HEADER_SIZE as u64 - 4))?;
+        self.output.write_all(&self.data_size.to_le_bytes())?;
+        self.output.flush()
+    }
+}
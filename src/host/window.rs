@@ -0,0 +1,86 @@
+use crate::guestsystem::components::display::{HEIGHT, WIDTH};
+
+const DEFAULT_MULTIPLIER: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scaling {
+    Integer,
+    Smooth,
+}
+
+impl Scaling {
+    pub fn from_name(name: &str) -> Option<Scaling> {
+        match name.to_lowercase().as_str() {
+            "integer" => Some(Scaling::Integer),
+            "smooth" => Some(Scaling::Smooth),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowConfig {
+    width: u32,
+    height: u32,
+    scaling: Scaling,
+    fullscreen: bool,
+}
+
+impl WindowConfig {
+    pub fn new(width: u32, height: u32, scaling: Scaling, fullscreen: bool) -> WindowConfig {
+        WindowConfig {
+            width,
+            height,
+            scaling,
+            fullscreen,
+        }
+    }
+
+    pub fn default() -> WindowConfig {
+        WindowConfig::new(
+            WIDTH as u32 * DEFAULT_MULTIPLIER,
+            HEIGHT as u32 * DEFAULT_MULTIPLIER,
+            Scaling::Integer,
+            false,
+        )
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn scaling(&self) -> Scaling {
+        self.scaling
+    }
+
+    pub fn fullscreen(&self) -> bool {
+        self.fullscreen
+    }
+
+    // The largest area with the screen's aspect ratio that fits in the output,
+    // centred, as (x, y, width, height).
+    pub fn letterbox(&self, output_width: u32, output_height: u32) -> (i32, i32, u32, u32) {
+        let (width, height) = match self.scaling {
+            Scaling::Integer => {
+                let scale: u32 = (output_width / WIDTH as u32)
+                    .min(output_height / HEIGHT as u32)
+                    .max(1);
+                (WIDTH as u32 * scale, HEIGHT as u32 * scale)
+            }
+            Scaling::Smooth => {
+                if output_width * (HEIGHT as u32) < output_height * (WIDTH as u32) {
+                    (output_width, output_width * HEIGHT as u32 / WIDTH as u32)
+                } else {
+                    (output_height * WIDTH as u32 / HEIGHT as u32, output_height)
+                }
+            }
+        };
+        let x: i32 = (output_width as i32 - width as i32) / 2;
+        let y: i32 = (output_height as i32 - height as i32) / 2;
+        (x, y, width, height)
+    }
+}
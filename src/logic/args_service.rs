@@ -1,6 +1,49 @@
-use std::{fs, io::stdin};
+use std::{
+    fs::{self, File},
+    io::{stdin, stdout, BufWriter, Write},
+};
 
-use crate::config::CpuConfig;
+#[cfg(feature = "sdl")]
+use crate::host::{
+    crt::CrtEffects,
+    sdl_frontend::SdlFrontend,
+    window::{Scaling, WindowConfig},
+};
+use crate::{
+    config::{CpuConfig, OpcodePolicy, StackPolicy, QUIRK_PROFILES},
+    error::EmulatorError,
+    guestsystem::{
+        components::random::{RandomKind, RandomSource, RANDOM_KINDS},
+        speed::{Speed, DEFAULT_SLOW_MOTION_FACTOR},
+    },
+    host::{
+        filter::{DisplayFilter, FilterKind},
+        frontend::Frontend,
+        headless_frontend::HeadlessFrontend,
+        palette::{Palette, PALETTE_SIZE},
+        recorder::Recorder,
+        screenshot::Screenshot,
+        settings::{Settings, DEFAULT_SETTINGS_PATH},
+        terminal_frontend::{TerminalFrontend, TerminalGlyphs},
+    },
+};
+
+use super::{
+    coverage::Coverage,
+    game_db::{rom_hash, GameInfo},
+    movie::Movie,
+    octo::compiler::{OctoCompiler, SourceMap},
+    profiler::Profiler,
+    rom_watcher::RomWatcher,
+    tracer::Tracer,
+};
+
+const OCTO_EXTENSION: &str = ".8o";
+// Larger screenshots and recordings would take gigabytes per frame.
+const MAX_SCALE: u32 = 64;
+// Slower than this, a single frame would take seconds to show.
+const MAX_SLOW_MOTION_FACTOR: f64 = 100.0;
+const PALETTE_KEYS: [&str; PALETTE_SIZE] = ["background", "foreground"];
 
 pub struct ArgsService;
 
@@ -17,24 +60,487 @@ impl ArgsService {
         path.to_string()
     }
 
-    pub fn read_rom(&self, rom_path: &str) -> Result<Vec<u8>, &str> {
+    pub fn read_rom(&self, rom_path: &str) -> Result<(Vec<u8>, Option<SourceMap>), EmulatorError> {
         if rom_path == "" {
-            return Err("No path provided for ROM.");
+            return Err(EmulatorError::MissingRomPath);
         }
-        let file_contents_res = fs::read(rom_path);
-        if file_contents_res.is_err() {
-            return Err("File not found.");
+        if rom_path.ends_with(OCTO_EXTENSION) {
+            return self
+                .compile_octo(rom_path)
+                .map(|(rom, source_map)| (rom, Some(source_map)));
         }
-        Ok(file_contents_res.unwrap())
+        match fs::read(rom_path) {
+            Ok(rom) => Ok((rom, None)),
+            Err(source) => Err(EmulatorError::Io {
+                path: rom_path.to_string(),
+                source,
+            }),
+        }
+    }
+
+    fn compile_octo(&self, source_path: &str) -> Result<(Vec<u8>, SourceMap), EmulatorError> {
+        let source: String = match fs::read_to_string(source_path) {
+            Ok(source) => source,
+            Err(source) => {
+                return Err(EmulatorError::Io {
+                    path: source_path.to_string(),
+                    source,
+                })
+            }
+        };
+        OctoCompiler::new(&source)
+            .compile()
+            .map_err(|source| EmulatorError::Compile {
+                path: source_path.to_string(),
+                source,
+            })
     }
 
     pub fn find_config_arg(&self, args: &[String]) -> bool {
-        if args.len() >= 3 {
-            if args[2] == "--config" {
-                return true;
+        self.has_flag(args, "--config")
+    }
+
+    pub fn find_quirk_profile(&self, args: &[String]) -> Result<Option<CpuConfig>, String> {
+        match self.find_option(args, "--quirks") {
+            Some(name) => match CpuConfig::profile(&name) {
+                Some(cpu_config) => Ok(Some(cpu_config)),
+                None => Err(format!(
+                    "Unknown quirk profile '{}', expected one of: {}.",
+                    name,
+                    QUIRK_PROFILES.join(", ")
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn apply_stack_args(
+        &self,
+        args: &[String],
+        cpu_config: &mut CpuConfig,
+    ) -> Result<(), String> {
+        if let Some(depth) = self.find_option(args, "--stack-depth") {
+            match depth.parse::<usize>() {
+                Ok(depth) if depth > 0 => cpu_config.set_stack_depth(depth),
+                _ => return Err(format!("Invalid stack depth '{}'.", depth)),
+            }
+        }
+        if let Some(policy) = self.find_option(args, "--stack-policy") {
+            match StackPolicy::from_name(&policy) {
+                Some(policy) => cpu_config.set_stack_policy(policy),
+                None => {
+                    return Err(format!(
+                        "Invalid stack policy '{}', expected halt, wrap or debug.",
+                        policy
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply_quirk_args(&self, args: &[String], cpu_config: &mut CpuConfig) {
+        if self.has_flag(args, "--wrap-sprites") {
+            cpu_config.set_wrap_sprites(true);
+        }
+        if self.has_flag(args, "--clip-sprites") {
+            cpu_config.set_wrap_sprites(false);
+        }
+        if self.has_flag(args, "--display-wait") {
+            cpu_config.set_display_wait(true);
+        }
+        if self.has_flag(args, "--no-display-wait") {
+            cpu_config.set_display_wait(false);
+        }
+        if self.has_flag(args, "--vf-reset") {
+            cpu_config.set_vf_reset(true);
+        }
+        if self.has_flag(args, "--no-vf-reset") {
+            cpu_config.set_vf_reset(false);
+        }
+    }
+
+    pub fn apply_opcode_args(
+        &self,
+        args: &[String],
+        cpu_config: &mut CpuConfig,
+    ) -> Result<(), String> {
+        if let Some(policy) = self.find_option(args, "--opcode-policy") {
+            match OpcodePolicy::from_name(&policy) {
+                Some(policy) => cpu_config.set_opcode_policy(policy),
+                None => {
+                    return Err(format!(
+                        "Invalid opcode policy '{}', expected ignore, warn, halt or debug.",
+                        policy
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The terminal is used when asked for, or when SDL is not compiled in.
+    pub fn find_frontend(
+        &self,
+        args: &[String],
+        palette: Palette,
+        title: Option<&str>,
+        settings: &Settings,
+    ) -> Result<Box<dyn Frontend>, String> {
+        if self.has_flag(args, "--headless") {
+            return match self.find_option(args, "--frames").map(|n| n.parse::<u64>()) {
+                Some(Ok(frames)) => Ok(Box::new(HeadlessFrontend::new(frames))),
+                _ => Err("Headless mode needs a frame count: --frames N.".to_string()),
+            };
+        }
+        if self.has_flag(args, "--terminal") || !cfg!(feature = "sdl") {
+            let glyphs: TerminalGlyphs = if self.has_flag(args, "--braille") {
+                TerminalGlyphs::Braille
+            } else {
+                TerminalGlyphs::HalfBlocks
+            };
+            return match TerminalFrontend::new(glyphs, palette) {
+                Ok(frontend) => Ok(Box::new(frontend)),
+                Err(err) => Err(format!("Cannot open the terminal: {}", err)),
+            };
+        }
+        self.sdl_frontend(args, palette, title, settings)
+    }
+
+    // A comma separated list like "scanlines:0.4,bloom", from the command line or the settings file.
+    #[cfg(feature = "sdl")]
+    fn find_crt(&self, args: &[String], settings: &Settings) -> Result<CrtEffects, String> {
+        let list: String = match self.find_option(args, "--crt") {
+            Some(list) => list,
+            None => settings.get("crt").unwrap_or("").to_string(),
+        };
+        let mut crt: CrtEffects = CrtEffects::none();
+        for spec in list.split(',').filter(|spec| !spec.trim().is_empty()) {
+            if crt.set(spec).is_none() {
+                return Err(format!(
+                    "Invalid CRT effect '{}', expected scanlines, grid, bloom or curvature with an optional :AMOUNT from 0 to 1.",
+                    spec
+                ));
+            }
+        }
+        Ok(crt)
+    }
+
+    // Command line options win over the settings file.
+    #[cfg(feature = "sdl")]
+    fn find_window_config(
+        &self,
+        args: &[String],
+        settings: &Settings,
+    ) -> Result<WindowConfig, String> {
+        let default: WindowConfig = WindowConfig::default();
+        let mut size: [u32; 2] = [default.width(), default.height()];
+        for (i, key) in ["window_width", "window_height"].iter().enumerate() {
+            if let Some(value) = settings.get(key) {
+                match value.parse::<u32>() {
+                    Ok(value) if value > 0 => size[i] = value,
+                    _ => return Err(format!("Invalid {} '{}'.", key, value)),
+                }
+            }
+        }
+        let scaling_name: Option<String> = self
+            .find_option(args, "--scaling")
+            .or_else(|| settings.get("scaling").map(|name| name.to_string()));
+        let scaling: Scaling = match scaling_name {
+            Some(name) => match Scaling::from_name(&name) {
+                Some(scaling) => scaling,
+                None => {
+                    return Err(format!(
+                        "Invalid scaling '{}', expected integer or smooth.",
+                        name
+                    ))
+                }
+            },
+            None => default.scaling(),
+        };
+        let fullscreen: bool =
+            self.has_flag(args, "--fullscreen") || settings.get("fullscreen") == Some("true");
+        Ok(WindowConfig::new(size[0], size[1], scaling, fullscreen))
+    }
+
+    #[cfg(feature = "sdl")]
+    fn sdl_frontend(
+        &self,
+        args: &[String],
+        palette: Palette,
+        title: Option<&str>,
+        settings: &Settings,
+    ) -> Result<Box<dyn Frontend>, String> {
+        let window_config: WindowConfig = self.find_window_config(args, settings)?;
+        let crt: CrtEffects = self.find_crt(args, settings)?;
+        let memory_viewer: bool = self.has_flag(args, "--memory-viewer");
+        match SdlFrontend::new(palette, title, window_config, crt, memory_viewer) {
+            Ok(frontend) => Ok(Box::new(frontend)),
+            Err(err) => Err(format!("Cannot open the SDL window: {}", err)),
+        }
+    }
+
+    #[cfg(not(feature = "sdl"))]
+    fn sdl_frontend(
+        &self,
+        _args: &[String],
+        _palette: Palette,
+        _title: Option<&str>,
+        _settings: &Settings,
+    ) -> Result<Box<dyn Frontend>, String> {
+        Err("RCHIP-8 was built without SDL, use --terminal.".to_string())
+    }
+
+    pub fn find_settings(&self, args: &[String]) -> Result<Settings, String> {
+        match self.find_option(args, "--settings") {
+            Some(path) => Settings::load(&path, true),
+            None => Settings::load(DEFAULT_SETTINGS_PATH, false),
+        }
+    }
+
+    // Command line colours win over the game database, which wins over the settings file.
+    pub fn find_palette(
+        &self,
+        args: &[String],
+        settings: &Settings,
+        game: Option<&GameInfo>,
+    ) -> Result<Palette, String> {
+        let mut palette: Palette = Palette::default();
+        if let Some(theme) = settings.get("theme") {
+            palette = self.find_theme(theme)?;
+        }
+        for (index, key) in PALETTE_KEYS.iter().enumerate() {
+            if let Some(hex) = settings.get(key) {
+                match Palette::parse_color(hex) {
+                    Some(color) => palette.set_color(index, color),
+                    None => return Err(format!("Invalid {} colour '{}'.", key, hex)),
+                }
+            }
+        }
+        if let Some(theme) = game.and_then(|game| game.theme) {
+            palette = self.find_theme(theme)?;
+        }
+        if let Some(theme) = self.find_option(args, "--theme") {
+            palette = self.find_theme(&theme)?;
+        }
+        if let Some(colors) = self.find_option(args, "--palette") {
+            let hexes: Vec<&str> = colors.split(',').collect();
+            if hexes.len() > PALETTE_SIZE {
+                return Err(format!(
+                    "At most {} palette colours are used.",
+                    PALETTE_SIZE
+                ));
+            }
+            for (index, hex) in hexes.iter().enumerate() {
+                match Palette::parse_color(hex) {
+                    Some(color) => palette.set_color(index, color),
+                    None => return Err(format!("Invalid colour '{}'.", hex)),
+                }
+            }
+        }
+        Ok(palette)
+    }
+
+    fn find_theme(&self, name: &str) -> Result<Palette, String> {
+        match Palette::theme(name) {
+            Some(palette) => Ok(palette),
+            None => Err(format!(
+                "Unknown theme '{}', expected one of: {}.",
+                name,
+                Palette::theme_names().join(", ")
+            )),
+        }
+    }
+
+    pub fn find_filter(
+        &self,
+        args: &[String],
+        settings: &Settings,
+    ) -> Result<DisplayFilter, String> {
+        let spec: String = match self.find_option(args, "--filter") {
+            Some(spec) => spec,
+            None => settings.get("filter").unwrap_or("none").to_string(),
+        };
+        match FilterKind::from_spec(&spec) {
+            Some(kind) => Ok(DisplayFilter::new(kind)),
+            None => Err(format!(
+                "Invalid filter '{}', expected none, blend[:FRAMES], phosphor[:DECAY] or deflicker[:FRAMES].",
+                spec
+            )),
+        }
+    }
+
+    pub fn find_screenshot(&self, args: &[String], palette: Palette) -> Result<Screenshot, String> {
+        let scale: u32 = self.find_scale(args, "--screenshot-scale")?;
+        let mut screenshot: Screenshot = Screenshot::new(scale, palette);
+        if let Some(path) = self.find_option(args, "--screenshot") {
+            screenshot.set_exit_path(path);
+        }
+        Ok(screenshot)
+    }
+
+    pub fn find_recorder(&self, args: &[String], palette: Palette) -> Result<Recorder, String> {
+        let scale: u32 = self.find_scale(args, "--record-scale")?;
+        Ok(Recorder::new(
+            self.find_option(args, "--record"),
+            scale,
+            palette,
+        ))
+    }
+
+    pub fn find_random(&self, args: &[String]) -> Result<RandomSource, String> {
+        let kind: RandomKind = match self.find_option(args, "--rng") {
+            Some(name) => RandomKind::from_name(&name).ok_or(format!(
+                "Unknown random number source '{}', expected one of: {}.",
+                name,
+                RANDOM_KINDS.join(", ")
+            ))?,
+            None => RandomKind::Std,
+        };
+        let seed: u64 = match self.find_option(args, "--seed") {
+            Some(seed) => seed
+                .parse()
+                .map_err(|_| format!("Invalid seed '{}'.", seed))?,
+            None => rand::random(),
+        };
+        Ok(RandomSource::new(kind, seed))
+    }
+
+    pub fn find_movie(
+        &self,
+        args: &[String],
+        rom: &[u8],
+        random: &RandomSource,
+        cpu_config: &CpuConfig,
+    ) -> Result<Option<Movie>, String> {
+        if let Some(path) = self.find_option(args, "--play-movie") {
+            let movie: Movie = Movie::load(&path)?;
+            movie.check_rom(rom)?;
+            return Ok(Some(movie));
+        }
+        Ok(self.find_option(args, "--record-movie").map(|path| {
+            Movie::record(
+                &path,
+                rom_hash(rom),
+                random.get_kind(),
+                random.get_seed(),
+                cpu_config,
+            )
+        }))
+    }
+
+    pub fn find_tracer(&self, args: &[String]) -> Result<Option<Tracer>, String> {
+        let trace_file: Option<String> = self.find_option(args, "--trace-file");
+        if !self.has_flag(args, "--trace") && trace_file.is_none() {
+            return Ok(None);
+        }
+        let output: Box<dyn Write> = match trace_file {
+            Some(path) => match File::create(&path) {
+                Ok(file) => Box::new(BufWriter::new(file)),
+                Err(_) => return Err(format!("Cannot create trace file {}.", path)),
+            },
+            None => Box::new(BufWriter::new(stdout())),
+        };
+        let pc_range: Option<(u16, u16)> = match self.find_option(args, "--trace-pc") {
+            Some(range) => {
+                let (from, to) = self.parse_range(&range, 16)?;
+                match (u16::try_from(from), u16::try_from(to)) {
+                    (Ok(from), Ok(to)) => Some((from, to)),
+                    _ => {
+                        return Err(format!(
+                            "Invalid PC range '{}', addresses go up to FFFF.",
+                            range
+                        ))
+                    }
+                }
             }
+            None => None,
+        };
+        let cycle_range: Option<(u64, u64)> = match self.find_option(args, "--trace-cycles") {
+            Some(range) => Some(self.parse_range(&range, 10)?),
+            None => None,
+        };
+        Ok(Some(Tracer::new(output, pc_range, cycle_range)))
+    }
+
+    pub fn find_profiler(&self, args: &[String]) -> Option<Profiler> {
+        let folded_path: Option<String> = self.find_option(args, "--profiler-folded");
+        if !self.has_flag(args, "--profiler") && folded_path.is_none() {
+            return None;
+        }
+        Some(Profiler::new(folded_path))
+    }
+
+    pub fn find_coverage(&self, args: &[String]) -> Option<Coverage> {
+        let listing_path: String = self.find_option(args, "--coverage")?;
+        let lcov_path: Option<String> = self.find_option(args, "--coverage-lcov");
+        Some(Coverage::new(listing_path, lcov_path))
+    }
+
+    pub fn find_speed(&self, args: &[String]) -> Result<Speed, String> {
+        let slow_motion_factor: f64 = match self.find_option(args, "--slow-motion") {
+            Some(factor) => match factor.parse::<f64>() {
+                Ok(factor)
+                    if factor.is_finite() && (1.0..=MAX_SLOW_MOTION_FACTOR).contains(&factor) =>
+                {
+                    factor
+                }
+                _ => {
+                    return Err(format!(
+                        "Invalid slow motion factor '{}', expected a number from 1 to {}.",
+                        factor, MAX_SLOW_MOTION_FACTOR
+                    ))
+                }
+            },
+            None => DEFAULT_SLOW_MOTION_FACTOR,
+        };
+        Ok(Speed::new(
+            slow_motion_factor,
+            self.has_flag(args, "--uncapped"),
+        ))
+    }
+
+    pub fn find_watcher(&self, args: &[String], rom_path: &str) -> Option<RomWatcher> {
+        if !self.has_flag(args, "--watch") {
+            return None;
+        }
+        Some(RomWatcher::new(rom_path))
+    }
+
+    fn has_flag(&self, args: &[String], flag: &str) -> bool {
+        args.iter().skip(2).any(|arg| arg == flag)
+    }
+
+    fn find_scale(&self, args: &[String], name: &str) -> Result<u32, String> {
+        match self.find_option(args, name) {
+            Some(scale) => match scale.parse::<u32>() {
+                Ok(scale) if (1..=MAX_SCALE).contains(&scale) => Ok(scale),
+                _ => Err(format!(
+                    "Invalid scale '{}' for {}, expected 1 to {}.",
+                    scale, name, MAX_SCALE
+                )),
+            },
+            None => Ok(1),
+        }
+    }
+
+    fn find_option(&self, args: &[String], name: &str) -> Option<String> {
+        let position: usize = args.iter().skip(2).position(|arg| arg == name)?;
+        args.get(position + 3).cloned()
+    }
+
+    fn parse_range(&self, range: &str, radix: u32) -> Result<(u64, u64), String> {
+        let invalid: String = format!("Invalid range '{}', expected FROM-TO.", range);
+        let (from, to) = match range.split_once('-') {
+            Some(bounds) => bounds,
+            None => return Err(invalid),
+        };
+        let from = u64::from_str_radix(from.trim_start_matches("0x"), radix);
+        let to = u64::from_str_radix(to.trim_start_matches("0x"), radix);
+        match (from, to) {
+            (Ok(from), Ok(to)) if from <= to => Ok((from, to)),
+            _ => Err(invalid),
         }
-        return false;
     }
 
     pub fn prompt_config(&self) -> CpuConfig {
@@ -50,7 +556,25 @@ impl ArgsService {
         );
         let modern_index_addition: bool =
             self.prompt_config_option("  -> modern addition to index register? (Y/N) default Y");
-        CpuConfig::new(modern_shift, modern_jump_offset, modern_store_and_load, modern_index_addition)
+        let wrap_sprites: bool = self.prompt_config_option(
+            "  -> sprites wrapping around the screen edges instead of clipping? (Y/N) - default: N",
+        );
+        let display_wait: bool = self.prompt_config_option(
+            "  -> waiting for the next frame after drawing a sprite? (Y/N) - default: N",
+        );
+        let vf_reset: bool = self.prompt_config_option(
+            "  -> resetting VF after the logic instructions (8XY1, 8XY2, 8XY3)? (Y/N) - default: N",
+        );
+        let mut cpu_config: CpuConfig = CpuConfig::new(
+            modern_shift,
+            modern_jump_offset,
+            modern_store_and_load,
+            modern_index_addition,
+        );
+        cpu_config.set_wrap_sprites(wrap_sprites);
+        cpu_config.set_display_wait(display_wait);
+        cpu_config.set_vf_reset(vf_reset);
+        cpu_config
     }
 
     fn read_line(&self) -> String {
@@ -0,0 +1,292 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use sha1::{Digest, Sha1};
+
+use crate::{
+    config::CpuConfig,
+    guestsystem::{
+        components::{
+            cpu::Cpu,
+            display::DisplayScreen,
+            memory::Memory,
+            random::{RandomKind, RandomSource},
+        },
+        guest_system::GuestSystem,
+    },
+    host::headless_frontend::HeadlessFrontend,
+};
+
+use super::{args_service::ArgsService, interpreter::Interpreter, movie::Movie};
+
+pub const DEFAULT_MANIFEST_PATH: &str = "roms/tests/conformance.txt";
+// Written in place of a golden hash that has not been recorded yet.
+const UNRECORDED_HASH: &str = "-";
+
+// One line of the manifest: ROM path (relative to the manifest), quirk
+// profile, number of frames to run, SHA-1 of the final framebuffer and
+// optionally an input movie to play back.
+pub struct ConformanceCase {
+    rom: String,
+    profile: String,
+    frames: u64,
+    hash: Option<String>,
+    movie: Option<String>,
+}
+
+enum CaseResult {
+    Pass,
+    Fail(String),
+    Unrecorded(String),
+    Skipped(String),
+}
+
+// Runs every case of the manifest and prints one line per ROM and quirk
+// profile. Returns whether no case failed. Missing ROMs and unrecorded
+// goldens count as failures unless they are allowed.
+pub fn run_manifest(manifest_path: &str, allow_missing: bool) -> Result<bool, String> {
+    let cases: Vec<ConformanceCase> = load_manifest(manifest_path)?;
+    let rom_dir: &Path = Path::new(manifest_path)
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+    let (mut passed, mut failed, mut unrecorded, mut skipped) = (0, 0, 0, 0);
+    for case in &cases {
+        let name: String = format!("{} [{}]", case.rom, case.profile);
+        match run_case(case, rom_dir) {
+            CaseResult::Pass => {
+                passed += 1;
+                println!("PASS  {}", name);
+            }
+            CaseResult::Fail(msg) => {
+                failed += 1;
+                println!("FAIL  {}: {}", name, msg);
+            }
+            CaseResult::Unrecorded(hash) => {
+                unrecorded += 1;
+                println!("NEW   {}: framebuffer {}", name, hash);
+            }
+            CaseResult::Skipped(msg) => {
+                skipped += 1;
+                println!("SKIP  {}: {}", name, msg);
+            }
+        }
+    }
+    println!(
+        "{} passed, {} failed, {} unrecorded, {} skipped",
+        passed, failed, unrecorded, skipped
+    );
+    let incomplete: bool = unrecorded + skipped > 0;
+    if incomplete && !allow_missing {
+        println!(
+            "Missing ROMs and unrecorded goldens fail the run unless --allow-missing is given."
+        );
+    }
+    Ok(failed == 0 && (allow_missing || !incomplete))
+}
+
+pub fn load_manifest(manifest_path: &str) -> Result<Vec<ConformanceCase>, String> {
+    let contents: String = fs::read_to_string(manifest_path)
+        .map_err(|err| format!("Cannot read manifest {}: {}", manifest_path, err))?;
+    let mut cases: Vec<ConformanceCase> = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let frames: Option<u64> = fields.get(2).and_then(|frames| frames.parse().ok());
+        match (fields.len(), frames) {
+            (4..=5, Some(frames)) => cases.push(ConformanceCase {
+                rom: fields[0].to_string(),
+                profile: fields[1].to_string(),
+                frames,
+                hash: match fields[3] {
+                    UNRECORDED_HASH => None,
+                    hash => Some(hash.to_lowercase()),
+                },
+                movie: fields.get(4).map(|movie| movie.to_string()),
+            }),
+            _ => {
+                return Err(format!(
+                    "{}:{}: expected 'ROM PROFILE FRAMES HASH [MOVIE]'.",
+                    manifest_path,
+                    index + 1
+                ))
+            }
+        }
+    }
+    Ok(cases)
+}
+
+fn run_case(case: &ConformanceCase, rom_dir: &Path) -> CaseResult {
+    let rom_path: PathBuf = rom_dir.join(&case.rom);
+    if !rom_path.exists() {
+        return CaseResult::Skipped(format!("{} not found", rom_path.display()));
+    }
+    let cpu_config: CpuConfig = match CpuConfig::profile(&case.profile) {
+        Some(cpu_config) => cpu_config,
+        None => return CaseResult::Fail(format!("unknown quirk profile '{}'", case.profile)),
+    };
+    let (rom_bytes, _) = match ArgsService::new().read_rom(&rom_path.to_string_lossy()) {
+        Ok(rom) => rom,
+        Err(err) => return CaseResult::Fail(err.to_string()),
+    };
+
+    let movie: Option<Movie> = match &case.movie {
+        Some(movie) => match Movie::load(&rom_dir.join(movie).to_string_lossy()) {
+            Ok(movie) => Some(movie),
+            Err(msg) => return CaseResult::Fail(msg),
+        },
+        None => None,
+    };
+    // The manifest picks the profile, so a movie recorded with another one fails.
+    if let Some(movie) = &movie {
+        if let Err(msg) = movie
+            .check_rom(&rom_bytes)
+            .and_then(|_| movie.check_config(&cpu_config))
+        {
+            return CaseResult::Fail(msg);
+        }
+    }
+
+    let memory: Memory = Memory::with_stack_depth(cpu_config.stack_depth());
+    let mut cpu: Cpu = Cpu::new(cpu_config);
+    cpu.set_random(match &movie {
+        Some(movie) => RandomSource::new(movie.get_random_kind(), movie.get_seed()),
+        None => RandomSource::new(RandomKind::Std, 0),
+    });
+    let mut guest_system: GuestSystem = GuestSystem::new(memory, cpu);
+    if let Some(movie) = movie {
+        guest_system.set_movie(movie);
+    }
+    let interpreter: Interpreter = Interpreter::new();
+    let mut frontend: HeadlessFrontend = HeadlessFrontend::quiet(case.frames);
+    if let Err(err) = guest_system.run_program(&rom_bytes, &interpreter, &mut frontend) {
+        return CaseResult::Fail(err.to_string());
+    }
+
+    let actual: String = framebuffer_hash(guest_system.get_display());
+    match &case.hash {
+        Some(expected) if *expected == actual => CaseResult::Pass,
+        Some(expected) => {
+            CaseResult::Fail(format!("framebuffer {}, expected {}", actual, expected))
+        }
+        None => CaseResult::Unrecorded(actual),
+    }
+}
+
+// SHA-1 of the screen in the resolution of the current mode, packed row by
+// row, eight pixels per byte, so lores hashes don't depend on the framebuffer size.
+pub fn framebuffer_hash(display: &DisplayScreen) -> String {
+    let (width, height) = display.get_size();
+    let mut packed: Vec<u8> = vec![0; width * height / 8];
+    for y in 0..height {
+        for x in 0..width {
+            if display.is_lit(x, y) {
+                let bit: usize = y * width + x;
+                packed[bit / 8] |= 0x80 >> (bit % 8);
+            }
+        }
+    }
+    Sha1::digest(&packed)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZERO: [u8; 5] = [0xF0, 0x90, 0x90, 0x90, 0xF0];
+    const ONE: [u8; 5] = [0x20, 0x60, 0x20, 0x20, 0x70];
+    const SEVEN: [u8; 5] = [0xF0, 0x10, 0x20, 0x40, 0x40];
+    const BOX: [u8; 8] = [0xFF, 0x81, 0x81, 0x81, 0x81, 0x81, 0x81, 0xFF];
+
+    // The lores screen the sprites should leave, drawn pixel by pixel
+    // without the emulator's own drawing code.
+    fn expected_screen(sprites: &[(usize, usize, &[u8])], wrap: bool) -> DisplayScreen {
+        let mut display: DisplayScreen = DisplayScreen::new();
+        let mut lit: [[bool; 32]; 64] = [[false; 32]; 64];
+        for (x, y, sprite) in sprites {
+            for (row, data) in sprite.iter().enumerate() {
+                for bit in 0..8 {
+                    let (px, py) = (x + bit, y + row);
+                    if data & (0x80 >> bit) == 0 || (!wrap && (px >= 64 || py >= 32)) {
+                        continue;
+                    }
+                    lit[px % 64][py % 32] ^= true;
+                }
+            }
+        }
+        for (x, column) in lit.iter().enumerate() {
+            for (y, on) in column.iter().enumerate() {
+                for (dx, dy) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+                    display.set_pixel(x * 2 + dx, y * 2 + dy, *on);
+                }
+            }
+        }
+        display
+    }
+
+    fn golden(rom: &str, profile: &str) -> String {
+        let manifest: String = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), DEFAULT_MANIFEST_PATH);
+        load_manifest(&manifest)
+            .unwrap()
+            .into_iter()
+            .find(|case| case.rom == rom && case.profile == profile)
+            .and_then(|case| case.hash)
+            .unwrap()
+    }
+
+    // The goldens of the repository's own test ROMs match the screens their
+    // comments describe, not just what the emulator happened to draw.
+    #[test]
+    fn flags_golden_shows_the_documented_flags() {
+        let digits = |flags: [&'static [u8]; 6]| -> Vec<(usize, usize, &'static [u8])> {
+            flags
+                .iter()
+                .enumerate()
+                .map(|(index, digit)| (1 + index * 5, 1, *digit))
+                .collect()
+        };
+        let vip: Vec<(usize, usize, &[u8])> = digits([&ONE, &ONE, &ZERO, &ONE, &ONE, &ZERO]);
+        let modern: Vec<(usize, usize, &[u8])> = digits([&ONE, &ONE, &ZERO, &ONE, &ONE, &SEVEN]);
+        assert_eq!(
+            framebuffer_hash(&expected_screen(&vip, false)),
+            golden("flags.8o", "vip")
+        );
+        assert_eq!(
+            framebuffer_hash(&expected_screen(&modern, false)),
+            golden("flags.8o", "modern")
+        );
+    }
+
+    #[test]
+    fn edge_golden_shows_the_documented_boxes() {
+        let boxes: [(usize, usize, &[u8]); 2] = [(60, 28, &BOX), (36, 10, &BOX)];
+        assert_eq!(
+            framebuffer_hash(&expected_screen(&boxes, false)),
+            golden("edge.8o", "modern")
+        );
+        assert_eq!(
+            framebuffer_hash(&expected_screen(&boxes, true)),
+            golden("edge.8o", "xochip")
+        );
+    }
+
+    // 0 and F are pressed together last, the key wait gets the later one.
+    #[test]
+    fn keys_golden_shows_the_keys_of_the_movie() {
+        const FIVE: [u8; 5] = [0xF0, 0x80, 0xF0, 0x10, 0xF0];
+        const A: [u8; 5] = [0xF0, 0x90, 0xF0, 0x90, 0x90];
+        const F: [u8; 5] = [0xF0, 0x80, 0xF0, 0x80, 0x80];
+        let keys: [(usize, usize, &[u8]); 3] = [(1, 1, &FIVE), (6, 1, &A), (11, 1, &F)];
+        assert_eq!(
+            framebuffer_hash(&expected_screen(&keys, false)),
+            golden("keys.8o", "modern")
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use crate::guestsystem::components::{
+    cpu::CpuInst,
+    memory::{Memory, PROGRAM_ADDRESS},
+};
+
+use super::{interpreter::Interpreter, octo::compiler::SourceMap};
+
+const ADDRESS_COUNT: usize = 4096;
+const CODE: u8 = 0b001;
+const READ: u8 = 0b010;
+const WRITTEN: u8 = 0b100;
+
+pub struct Coverage {
+    flags: [u8; ADDRESS_COUNT],
+    hits: [u64; ADDRESS_COUNT],
+    program_end: usize,
+    listing_path: String,
+    lcov_path: Option<String>,
+    source: Option<(String, SourceMap)>,
+}
+
+impl Coverage {
+    pub fn new(listing_path: String, lcov_path: Option<String>) -> Coverage {
+        Coverage {
+            flags: [0; ADDRESS_COUNT],
+            hits: [0; ADDRESS_COUNT],
+            program_end: PROGRAM_ADDRESS,
+            listing_path,
+            lcov_path,
+            source: None,
+        }
+    }
+
+    pub fn set_source_map(&mut self, source_path: &str, source_map: SourceMap) {
+        self.source = Some((source_path.to_string(), source_map));
+    }
+
+    pub fn set_program_length(&mut self, length: usize) {
+        self.program_end = (PROGRAM_ADDRESS + length).min(ADDRESS_COUNT);
+    }
+
+    pub fn record_fetch(&mut self, pc: u16) {
+        let address: usize = pc as usize % ADDRESS_COUNT;
+        self.hits[address] += 1;
+        self.flags[address] |= CODE;
+        self.flags[(address + 1) % ADDRESS_COUNT] |= CODE;
+    }
+
+    // Called before the instruction is executed, while I still holds the
+    // address the instruction is going to access. DXY0 reads a 16x16 sprite
+    // in hires.
+    pub fn record_access(&mut self, instruction: &CpuInst, index_register: u16, hires: bool) {
+        match instruction {
+            CpuInst::DisplayXYN(_, _, 0) if hires => self.mark(index_register, 32, READ),
+            CpuInst::DisplayXYN(_, _, n) => self.mark(index_register, *n as u16, READ),
+            CpuInst::LoadFromMemoryX(x) => self.mark(index_register, *x as u16 + 1, READ),
+            CpuInst::StoreInMemoryX(x) => self.mark(index_register, *x as u16 + 1, WRITTEN),
+            CpuInst::DecimalConversionX(_) => self.mark(index_register, 3, WRITTEN),
+            _ => {}
+        }
+    }
+
+    pub fn finish(&self, memory: &Memory, interpreter: &Interpreter) {
+        if let Err(err) = self.write_listing(memory, interpreter) {
+            println!(
+                "Cannot write coverage listing to {}: {}",
+                self.listing_path, err
+            );
+        }
+        if let Some(lcov_path) = &self.lcov_path {
+            match &self.source {
+                Some((source_path, source_map)) => {
+                    if let Err(err) = self.write_lcov(lcov_path, source_path, source_map) {
+                        println!("Cannot write lcov report to {}: {}", lcov_path, err);
+                    }
+                }
+                None => println!("No source map for this ROM, the lcov report was skipped."),
+            }
+        }
+    }
+
+    fn mark(&mut self, from: u16, length: u16, flag: u8) {
+        for offset in 0..length {
+            self.flags[(from as usize + offset as usize) % ADDRESS_COUNT] |= flag;
+        }
+    }
+
+    fn write_listing(&self, memory: &Memory, interpreter: &Interpreter) -> io::Result<()> {
+        let mut file: BufWriter<File> = BufWriter::new(File::create(&self.listing_path)?);
+        let end: usize = self.listing_end();
+        let (mut code, mut data, mut untouched) = (0, 0, 0);
+        for address in PROGRAM_ADDRESS..self.program_end {
+            match self.flags[address] {
+                0 => untouched += 1,
+                flags if flags & CODE == CODE => code += 1,
+                _ => data += 1,
+            }
+        }
+        writeln!(file, "; RCHIP-8 coverage listing")?;
+        writeln!(
+            file,
+            "; program bytes: {} code, {} data, {} never touched",
+            code, data, untouched
+        )?;
+        writeln!(file, "; ADDR  BYTES  USE    HITS      DISASSEMBLY")?;
+
+        let heap: &[u8] = memory.get_heap();
+        let mut address: usize = PROGRAM_ADDRESS;
+        while address < end {
+            let flags: u8 = self.flags[address];
+            if flags & CODE == CODE && address + 1 < ADDRESS_COUNT {
+                let raw: u16 = (heap[address] as u16) << 8 | heap[address + 1] as u16;
+                let mnemonic: String = interpreter.mnemonic(&interpreter.decode(raw));
+                write!(
+                    file,
+                    "{:04X}  {:04X}   {:<6} {:<9} {}",
+                    address,
+                    raw,
+                    self.usage(flags),
+                    self.hits[address],
+                    mnemonic
+                )?;
+                if let Some((_, source_map)) = &self.source {
+                    if let Some(line) = source_map.line_at(address as u16) {
+                        write!(file, " ; line {}", line)?;
+                    }
+                }
+                writeln!(file)?;
+                address += 2;
+            } else {
+                writeln!(
+                    file,
+                    "{:04X}  {:02X}     {:<6}           {}",
+                    address,
+                    heap[address],
+                    self.usage(flags),
+                    self.data_comment(flags)
+                )?;
+                address += 1;
+            }
+        }
+        file.flush()
+    }
+
+    fn write_lcov(
+        &self,
+        lcov_path: &str,
+        source_path: &str,
+        source_map: &SourceMap,
+    ) -> io::Result<()> {
+        let mut line_hits: BTreeMap<usize, u64> = BTreeMap::new();
+        for (address, line) in source_map.lines() {
+            let hits: u64 = self.hits[address as usize % ADDRESS_COUNT];
+            let entry: &mut u64 = line_hits.entry(line).or_insert(0);
+            *entry = (*entry).max(hits);
+        }
+        let mut file: BufWriter<File> = BufWriter::new(File::create(lcov_path)?);
+        writeln!(file, "TN:")?;
+        writeln!(file, "SF:{}", source_path)?;
+        for (line, hits) in line_hits.iter() {
+            writeln!(file, "DA:{},{}", line, hits)?;
+        }
+        writeln!(file, "LF:{}", line_hits.len())?;
+        writeln!(
+            file,
+            "LH:{}",
+            line_hits.values().filter(|hits| **hits > 0).count()
+        )?;
+        writeln!(file, "end_of_record")?;
+        file.flush()
+    }
+
+    fn listing_end(&self) -> usize {
+        let last_touched: usize = (PROGRAM_ADDRESS..ADDRESS_COUNT)
+            .rev()
+            .find(|address| self.flags[*address] != 0)
+            .map_or(PROGRAM_ADDRESS, |address| address + 1);
+        last_touched.max(self.program_end)
+    }
+
+    fn usage(&self, flags: u8) -> &str {
+        match (
+            flags & CODE == CODE,
+            flags & READ == READ,
+            flags & WRITTEN == WRITTEN,
+        ) {
+            (true, _, true) => "code*",
+            (true, _, _) => "code",
+            (false, true, true) => "data*",
+            (false, true, false) => "data",
+            (false, false, true) => "write",
+            (false, false, false) => "-",
+        }
+    }
+
+    fn data_comment(&self, flags: u8) -> &str {
+        match flags {
+            0 => "; never touched",
+            _ if flags & WRITTEN == WRITTEN => "; written via I",
+            _ => "; read via I (sprite/data)",
+        }
+    }
+}
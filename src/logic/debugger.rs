@@ -0,0 +1,119 @@
+use std::io::stdin;
+
+use crate::guestsystem::components::{cpu::Cpu, memory::Memory};
+
+use super::interpreter::Interpreter;
+
+const DUMP_ROW_LENGTH: usize = 16;
+const DEFAULT_DUMP_LENGTH: usize = 64;
+
+pub enum DebuggerAction {
+    Continue,
+    Step,
+    Quit,
+}
+
+pub struct Debugger;
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger
+    }
+
+    pub fn break_into(
+        &self,
+        reason: &str,
+        cpu: &Cpu,
+        memory: &Memory,
+        interpreter: &Interpreter,
+    ) -> DebuggerAction {
+        println!("-- Debugger: {} --", reason);
+        self.print_state(cpu, memory, interpreter);
+        loop {
+            println!("  (c)ontinue, (s)tep, (r)egisters, (m)emory ADDR [LEN], (q)uit");
+            // Without input left, as when stdin is a pipe, there is nobody to ask.
+            let line: String = match self.read_line() {
+                Some(line) => line,
+                None => return DebuggerAction::Quit,
+            };
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.first().map(|word| word.to_lowercase()).as_deref() {
+                Some("c") | Some("continue") => return DebuggerAction::Continue,
+                Some("s") | Some("step") => return DebuggerAction::Step,
+                Some("q") | Some("quit") => return DebuggerAction::Quit,
+                Some("r") | Some("registers") => self.print_state(cpu, memory, interpreter),
+                Some("m") | Some("memory") => self.dump_memory(&words[1..], memory),
+                _ => println!("  Unknown command."),
+            }
+        }
+    }
+
+    fn print_state(&self, cpu: &Cpu, memory: &Memory, interpreter: &Interpreter) {
+        let pc: u16 = cpu.get_pc();
+        let next: String = match interpreter.fetch(memory, pc) {
+            Ok(raw) => interpreter.mnemonic(&interpreter.decode(raw)),
+            Err(_) => "-".to_string(),
+        };
+        println!(
+            "  PC:{:04X} ({})  I:{:04X}  DT:{:02X}  ST:{:02X}",
+            pc,
+            next,
+            cpu.get_index_register(),
+            cpu.get_delay_timer(),
+            cpu.get_sound_timer()
+        );
+        let registers: Vec<String> = cpu
+            .get_variable_registers()
+            .iter()
+            .enumerate()
+            .map(|(i, v)| format!("V{:X}:{:02X}", i, v))
+            .collect();
+        println!("  {}", registers.join(" "));
+        let stack: Vec<String> = memory
+            .get_stack()
+            .iter()
+            .map(|address| format!("{:04X}", address))
+            .collect();
+        println!(
+            "  Stack ({}/{}): {}",
+            memory.get_stack_pointer(),
+            memory.get_stack_depth(),
+            stack.join(" ")
+        );
+    }
+
+    fn dump_memory(&self, args: &[&str], memory: &Memory) {
+        let address: Option<usize> = args
+            .first()
+            .and_then(|arg| usize::from_str_radix(arg.trim_start_matches("0x"), 16).ok());
+        let length: usize = args
+            .get(1)
+            .and_then(|arg| arg.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_DUMP_LENGTH);
+        let start: usize = match address {
+            Some(address) if address < memory.get_heap_size() => address,
+            _ => {
+                println!("  Please give a hexadecimal address below 0x1000.");
+                return;
+            }
+        };
+        let end: usize = start.saturating_add(length).min(memory.get_heap_size());
+        let heap: &[u8] = memory.get_heap();
+        for row in (start..end).step_by(DUMP_ROW_LENGTH) {
+            let bytes: Vec<String> = heap[row..(row + DUMP_ROW_LENGTH).min(end)]
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect();
+            println!("  {:04X}: {}", row, bytes.join(" "));
+        }
+    }
+
+    // None at the end of input.
+    fn read_line(&self) -> Option<String> {
+        let mut s = String::new();
+        match stdin().read_line(&mut s) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(s.trim().to_string()),
+        }
+    }
+}
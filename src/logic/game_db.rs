@@ -0,0 +1,33 @@
+use sha1::{Digest, Sha1};
+
+pub struct GameInfo {
+    pub sha1: &'static str,
+    pub title: &'static str,
+    pub theme: Option<&'static str>,
+}
+
+// Known ROMs, keyed by the SHA-1 of the ROM file.
+const GAMES: &[GameInfo] = &[
+    GameInfo {
+        sha1: "1ba58656810b67fd131eb9af3e3987863bf26c90",
+        title: "IBM Logo",
+        theme: None,
+    },
+    GameInfo {
+        sha1: "b232ef880bd6060fb45fa6effed7edf0ae95670e",
+        title: "Pong (Paul Vervalin, 1990)",
+        theme: Some("green"),
+    },
+];
+
+pub fn rom_hash(rom: &[u8]) -> String {
+    Sha1::digest(rom)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn find_game(rom: &[u8]) -> Option<&'static GameInfo> {
+    let hash: String = rom_hash(rom);
+    GAMES.iter().find(|game| game.sha1 == hash)
+}
@@ -1,4 +1,7 @@
-use crate::guestsystem::components::{cpu::CpuInst, memory::Memory};
+use crate::{
+    error::EmulatorError,
+    guestsystem::components::{cpu::CpuInst, memory::Memory},
+};
 
 pub const FONT_SIZE: usize = 5;
 pub const ALL_FONT_COUNT: usize = 16;
@@ -32,10 +35,10 @@ impl Interpreter {
         ]
     }
 
-    pub fn fetch(&self, memory: &Memory, address: u16) -> u16 {
-        let byte_1: u8 = memory.at_address(address);
-        let byte_2: u8 = memory.at_address(address + 1);
-        ((byte_1 as u16) << 8) | byte_2 as u16
+    pub fn fetch(&self, memory: &Memory, address: u16) -> Result<u16, EmulatorError> {
+        let byte_1: u8 = memory.at_address(address)?;
+        let byte_2: u8 = memory.at_address(address.wrapping_add(1))?;
+        Ok(((byte_1 as u16) << 8) | byte_2 as u16)
     }
 
     pub fn prev_pc(&self, current_address: u16) -> u16 {
@@ -87,6 +90,11 @@ impl Interpreter {
         if raw & 0x00F0 == 0x00E0 {
             return CpuInst::Cls;
         }
+        match raw {
+            0x00FE => return CpuInst::Lores,
+            0x00FF => return CpuInst::Hires,
+            _ => {}
+        }
         CpuInst::ExecMlrNNN(self.make_nnn(raw))
     }
 
@@ -128,6 +136,49 @@ impl Interpreter {
         }
     }
 
+    pub fn mnemonic(&self, instruction: &CpuInst) -> String {
+        match instruction {
+            CpuInst::ExecMlrNNN(nnn) => format!("SYS 0x{:03X}", nnn),
+            CpuInst::Cls => "CLS".to_string(),
+            CpuInst::Lores => "LOW".to_string(),
+            CpuInst::Hires => "HIGH".to_string(),
+            CpuInst::JmpNNN(nnn) => format!("JP 0x{:03X}", nnn),
+            CpuInst::SubRoutineNNN(nnn) => format!("CALL 0x{:03X}", nnn),
+            CpuInst::SubRoutineReturn => "RET".to_string(),
+            CpuInst::SkipIfEqXNN(x, nn) => format!("SE V{:X}, 0x{:02X}", x, nn),
+            CpuInst::SkipIfNotEqXNN(x, nn) => format!("SNE V{:X}, 0x{:02X}", x, nn),
+            CpuInst::SkipIfEqXY(x, y) => format!("SE V{:X}, V{:X}", x, y),
+            CpuInst::SkipIfNotEqXY(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+            CpuInst::SetXNN(x, nn) => format!("LD V{:X}, 0x{:02X}", x, nn),
+            CpuInst::AddXNN(x, nn) => format!("ADD V{:X}, 0x{:02X}", x, nn),
+            CpuInst::SetXY(x, y) => format!("LD V{:X}, V{:X}", x, y),
+            CpuInst::BitOrXY(x, y) => format!("OR V{:X}, V{:X}", x, y),
+            CpuInst::BitAndXY(x, y) => format!("AND V{:X}, V{:X}", x, y),
+            CpuInst::BitXorXY(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+            CpuInst::AddXY(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+            CpuInst::SubtFromLeftXY(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+            CpuInst::SubtFromRightXY(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+            CpuInst::ShiftLeftXY(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+            CpuInst::ShiftRightXY(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+            CpuInst::SetIndexNNN(nnn) => format!("LD I, 0x{:03X}", nnn),
+            CpuInst::JmpOffsetNNN(nnn) => format!("JP V0, 0x{:03X}", nnn),
+            CpuInst::RandomXNN(x, nn) => format!("RND V{:X}, 0x{:02X}", x, nn),
+            CpuInst::DisplayXYN(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+            CpuInst::SkipIfKeyX(x) => format!("SKP V{:X}", x),
+            CpuInst::SkipIfNotKeyX(x) => format!("SKNP V{:X}", x),
+            CpuInst::SetRegToDelayX(x) => format!("LD V{:X}, DT", x),
+            CpuInst::SetDelayX(x) => format!("LD DT, V{:X}", x),
+            CpuInst::SetSoundX(x) => format!("LD ST, V{:X}", x),
+            CpuInst::AddToIndexX(x) => format!("ADD I, V{:X}", x),
+            CpuInst::WaitForKeyX(x) => format!("LD V{:X}, K", x),
+            CpuInst::SetIndexToFontX(x) => format!("LD F, V{:X}", x),
+            CpuInst::DecimalConversionX(x) => format!("LD B, V{:X}", x),
+            CpuInst::StoreInMemoryX(x) => format!("LD [I], V{:X}", x),
+            CpuInst::LoadFromMemoryX(x) => format!("LD V{:X}, [I]", x),
+            CpuInst::InvalidInstruction => "???".to_string(),
+        }
+    }
+
     pub fn make_x(&self, raw: u16) -> u8 {
         ((raw & 0x0F00) >> 8) as u8
     }
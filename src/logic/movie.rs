@@ -0,0 +1,326 @@
+use std::{collections::BTreeMap, fs};
+
+use crate::{
+    config::{CpuConfig, OpcodePolicy, Quirk, StackPolicy, QUIRKS},
+    guestsystem::components::random::RandomKind,
+};
+
+use super::game_db::rom_hash;
+
+const MOVIE_HEADER: &str = "# RCHIP-8 input movie";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MovieMode {
+    Record,
+    Play,
+}
+
+// Per-frame keypad state as a 16-bit mask (bit N is key N), together with the
+// random number source, the hash of the ROM and the machine settings it was
+// recorded with. Only the frames where the mask changes are stored:
+//
+//   rom <sha1>
+//   rng <std|vip>
+//   seed <seed>
+//   quirks <comma separated quirk names, or none>
+//   stack <depth> <policy>
+//   opcodes <policy>
+//   cycles <cycles per frame>
+//   frames <length>
+//   <frame> <mask in hex>
+//
+// Hand-written movies may leave out the ROM hash and the settings, which are
+// then not checked.
+pub struct Movie {
+    path: String,
+    mode: MovieMode,
+    rom_hash: String,
+    random_kind: RandomKind,
+    seed: u64,
+    quirks: Option<Vec<Quirk>>,
+    stack: Option<(usize, StackPolicy)>,
+    opcode_policy: Option<OpcodePolicy>,
+    cycles_per_frame: Option<u32>,
+    length: u64,
+    changes: BTreeMap<u64, u16>,
+    frame: u64,
+    keys: u16,
+}
+
+impl Movie {
+    pub fn record(
+        path: &str,
+        rom_hash: String,
+        random_kind: RandomKind,
+        seed: u64,
+        cpu_config: &CpuConfig,
+    ) -> Movie {
+        let quirks: Vec<Quirk> = QUIRKS
+            .iter()
+            .copied()
+            .filter(|quirk| cpu_config.quirk(*quirk))
+            .collect();
+        Movie {
+            path: path.to_string(),
+            mode: MovieMode::Record,
+            rom_hash,
+            random_kind,
+            seed,
+            quirks: Some(quirks),
+            stack: Some((cpu_config.stack_depth(), cpu_config.stack_policy())),
+            opcode_policy: Some(cpu_config.opcode_policy()),
+            cycles_per_frame: None,
+            length: 0,
+            changes: BTreeMap::new(),
+            frame: 0,
+            keys: 0,
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Movie, String> {
+        let text: String = fs::read_to_string(path)
+            .map_err(|err| format!("Cannot read movie {}: {}", path, err))?;
+        let mut movie: Movie = Movie {
+            path: path.to_string(),
+            mode: MovieMode::Play,
+            rom_hash: String::new(),
+            random_kind: RandomKind::Std,
+            seed: 0,
+            quirks: None,
+            stack: None,
+            opcode_policy: None,
+            cycles_per_frame: None,
+            length: 0,
+            changes: BTreeMap::new(),
+            frame: 0,
+            keys: 0,
+        };
+        for (number, line) in text.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("{}: line {} is not a movie entry.", path, number + 1);
+            let (key, value) = line.split_once(' ').ok_or_else(invalid)?;
+            let value: &str = value.trim();
+            match key {
+                "rom" => movie.rom_hash = value.to_lowercase(),
+                "rng" => movie.random_kind = RandomKind::from_name(value).ok_or_else(invalid)?,
+                "seed" => movie.seed = value.parse().map_err(|_| invalid())?,
+                "quirks" => {
+                    let mut quirks: Vec<Quirk> = Vec::new();
+                    for name in value.split(',').filter(|name| *name != "none") {
+                        quirks.push(Quirk::from_name(name.trim()).ok_or_else(invalid)?);
+                    }
+                    movie.quirks = Some(quirks);
+                }
+                "stack" => {
+                    let (depth, policy) = value.split_once(' ').ok_or_else(invalid)?;
+                    let depth: usize = depth.parse().map_err(|_| invalid())?;
+                    let policy: StackPolicy =
+                        StackPolicy::from_name(policy.trim()).ok_or_else(invalid)?;
+                    movie.stack = Some((depth, policy));
+                }
+                "opcodes" => {
+                    movie.opcode_policy = Some(OpcodePolicy::from_name(value).ok_or_else(invalid)?)
+                }
+                "cycles" => movie.cycles_per_frame = Some(value.parse().map_err(|_| invalid())?),
+                "frames" => movie.length = value.parse().map_err(|_| invalid())?,
+                frame => {
+                    let frame: u64 = frame.parse().map_err(|_| invalid())?;
+                    let keys: u16 = u16::from_str_radix(value, 16).map_err(|_| invalid())?;
+                    movie.changes.insert(frame, keys);
+                }
+            }
+        }
+        Ok(movie)
+    }
+
+    pub fn get_mode(&self) -> MovieMode {
+        self.mode
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_random_kind(&self) -> RandomKind {
+        self.random_kind
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_cycles_per_frame(&self) -> Option<u32> {
+        self.cycles_per_frame
+    }
+
+    pub fn set_cycles_per_frame(&mut self, cycles_per_frame: u32) {
+        self.cycles_per_frame = Some(cycles_per_frame);
+    }
+
+    // A movie without a ROM hash fits any ROM.
+    pub fn check_rom(&self, rom: &[u8]) -> Result<(), String> {
+        if !self.rom_hash.is_empty() && self.rom_hash != rom_hash(rom) {
+            return Err(format!(
+                "Movie {} was recorded with a different ROM.",
+                self.path
+            ));
+        }
+        Ok(())
+    }
+
+    // Replaces the quirks, stack and opcode settings with the recorded ones.
+    pub fn apply_config(&self, cpu_config: &mut CpuConfig) {
+        if let Some(quirks) = &self.quirks {
+            for quirk in QUIRKS {
+                if cpu_config.quirk(quirk) != quirks.contains(&quirk) {
+                    cpu_config.toggle_quirk(quirk);
+                }
+            }
+        }
+        if let Some((depth, policy)) = self.stack {
+            cpu_config.set_stack_depth(depth);
+            cpu_config.set_stack_policy(policy);
+        }
+        if let Some(policy) = self.opcode_policy {
+            cpu_config.set_opcode_policy(policy);
+        }
+    }
+
+    pub fn check_config(&self, cpu_config: &CpuConfig) -> Result<(), String> {
+        let mut recorded: CpuConfig = *cpu_config;
+        self.apply_config(&mut recorded);
+        if recorded != *cpu_config {
+            return Err(format!(
+                "Movie {} was recorded with other quirk, stack or opcode settings.",
+                self.path
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.mode == MovieMode::Play && self.frame >= self.length
+    }
+
+    // The key state of the next frame: the live keys while recording, the
+    // recorded ones while playing back.
+    pub fn next_keys(&mut self, live_keys: u16) -> u16 {
+        match self.mode {
+            MovieMode::Record => {
+                if live_keys != self.keys || self.frame == 0 {
+                    self.changes.insert(self.frame, live_keys);
+                }
+                self.keys = live_keys;
+                self.length = self.frame + 1;
+            }
+            MovieMode::Play => {
+                if let Some(keys) = self.changes.get(&self.frame) {
+                    self.keys = *keys;
+                }
+            }
+        }
+        self.frame += 1;
+        self.keys
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let mut lines: Vec<String> = vec![
+            MOVIE_HEADER.to_string(),
+            format!("rom {}", self.rom_hash),
+            format!("rng {}", self.random_kind.name()),
+            format!("seed {}", self.seed),
+        ];
+        if let Some(quirks) = &self.quirks {
+            let names: Vec<&str> = quirks.iter().map(|quirk| quirk.name()).collect();
+            if names.is_empty() {
+                lines.push("quirks none".to_string());
+            } else {
+                lines.push(format!("quirks {}", names.join(",")));
+            }
+        }
+        if let Some((depth, policy)) = self.stack {
+            lines.push(format!("stack {} {}", depth, policy.name()));
+        }
+        if let Some(policy) = self.opcode_policy {
+            lines.push(format!("opcodes {}", policy.name()));
+        }
+        if let Some(cycles_per_frame) = self.cycles_per_frame {
+            lines.push(format!("cycles {}", cycles_per_frame));
+        }
+        lines.push(format!("frames {}", self.length));
+        for (frame, keys) in &self.changes {
+            lines.push(format!("{} {:04x}", frame, keys));
+        }
+        let mut text: String = lines.join("\n");
+        text.push('\n');
+        fs::write(&self.path, text)
+            .map_err(|err| format!("Cannot save movie {}: {}", self.path, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn round_trips_the_keys_and_settings() {
+        let path: String = env::temp_dir()
+            .join("rchip8-round-trip.movie")
+            .to_string_lossy()
+            .to_string();
+        let mut cpu_config: CpuConfig = CpuConfig::profile("vip").unwrap();
+        cpu_config.set_stack_depth(12);
+        cpu_config.set_stack_policy(StackPolicy::Wrap);
+        cpu_config.set_opcode_policy(OpcodePolicy::Warn);
+        let mut movie: Movie = Movie::record(
+            &path,
+            rom_hash(&[0x12, 0x00]),
+            RandomKind::Vip,
+            7,
+            &cpu_config,
+        );
+        movie.set_cycles_per_frame(20);
+        for keys in [0x0000, 0x0020, 0x0020, 0x8001, 0x0000] {
+            movie.next_keys(keys);
+        }
+        movie.save().unwrap();
+        let mut loaded: Movie = Movie::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(loaded.get_mode(), MovieMode::Play);
+        assert!(loaded.check_rom(&[0x12, 0x00]).is_ok());
+        assert!(loaded.check_rom(&[0x13, 0x00]).is_err());
+        assert_eq!(loaded.get_random_kind(), RandomKind::Vip);
+        assert_eq!(loaded.get_seed(), 7);
+        assert_eq!(loaded.get_cycles_per_frame(), Some(20));
+        assert!(loaded.check_config(&cpu_config).is_ok());
+        assert!(loaded.check_config(&CpuConfig::default()).is_err());
+        let mut applied: CpuConfig = CpuConfig::default();
+        loaded.apply_config(&mut applied);
+        assert_eq!(applied, cpu_config);
+
+        let keys: Vec<u16> = (0..5).map(|_| loaded.next_keys(0xFFFF)).collect();
+        assert_eq!(keys, vec![0x0000, 0x0020, 0x0020, 0x8001, 0x0000]);
+        assert!(loaded.is_finished());
+    }
+
+    #[test]
+    fn hand_written_movies_fit_any_rom_and_settings() {
+        let path: String = env::temp_dir()
+            .join("rchip8-hand-written.movie")
+            .to_string_lossy()
+            .to_string();
+        fs::write(&path, "frames 2\n1 0002\n").unwrap();
+        let loaded: Movie = Movie::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert!(loaded.check_rom(&[0x12, 0x00]).is_ok());
+        assert!(loaded.check_config(&CpuConfig::default()).is_ok());
+        assert_eq!(loaded.get_cycles_per_frame(), None);
+    }
+}
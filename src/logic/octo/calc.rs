@@ -0,0 +1,295 @@
+use std::f64::consts::{E, PI};
+
+use super::tokenizer::Token;
+
+pub trait CalcContext {
+    fn lookup(&self, name: &str) -> Option<f64>;
+    fn byte_at(&self, address: usize) -> Option<u8>;
+}
+
+// Octo evaluates :calc expressions right to left without operator precedence,
+// so `1 + 2 * 3` is `1 + (2 * 3)` and `2 * 3 + 1` is `2 * (3 + 1)`.
+pub struct Calc<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    context: &'a dyn CalcContext,
+}
+
+impl<'a> Calc<'a> {
+    pub fn new(tokens: &'a [Token], context: &'a dyn CalcContext) -> Calc<'a> {
+        Calc {
+            tokens,
+            position: 0,
+            context,
+        }
+    }
+
+    pub fn evaluate(&mut self) -> Result<f64, String> {
+        let value: f64 = self.expression()?;
+        match self.tokens.get(self.position) {
+            Some(token) => Err(format!("Unexpected '{}' in expression.", token.text)),
+            None => Ok(value),
+        }
+    }
+
+    fn expression(&mut self) -> Result<f64, String> {
+        let left: f64 = self.term()?;
+        let operator: String = match self.tokens.get(self.position) {
+            Some(token) if token.text != ")" => token.text.clone(),
+            _ => return Ok(left),
+        };
+        if !self.is_binary(&operator) {
+            return Err(format!("Unknown binary operator '{}'.", operator));
+        }
+        self.position += 1;
+        let right: f64 = self.expression()?;
+        self.apply_binary(&operator, left, right)
+    }
+
+    fn term(&mut self) -> Result<f64, String> {
+        let token: &Token = match self.tokens.get(self.position) {
+            Some(token) => token,
+            None => return Err("Unexpected end of expression.".to_string()),
+        };
+        self.position += 1;
+        let text: &str = token.text.as_str();
+        if text == "(" {
+            let value: f64 = self.expression()?;
+            match self.tokens.get(self.position) {
+                Some(close) if close.text == ")" => {
+                    self.position += 1;
+                    return Ok(value);
+                }
+                _ => return Err("Expected ')' in expression.".to_string()),
+            }
+        }
+        if self.is_unary(text) {
+            let value: f64 = self.term()?;
+            return self.apply_unary(text, value);
+        }
+        if let Some(number) = parse_number(text) {
+            return Ok(number as f64);
+        }
+        match text {
+            "PI" => Ok(PI),
+            "E" => Ok(E),
+            _ => self
+                .context
+                .lookup(text)
+                .ok_or(format!("Undefined name '{}' in expression.", text)),
+        }
+    }
+
+    fn is_unary(&self, operator: &str) -> bool {
+        matches!(
+            operator,
+            "-" | "~"
+                | "!"
+                | "sin"
+                | "cos"
+                | "tan"
+                | "exp"
+                | "log"
+                | "abs"
+                | "sqrt"
+                | "sign"
+                | "ceil"
+                | "floor"
+                | "@"
+        )
+    }
+
+    fn is_binary(&self, operator: &str) -> bool {
+        matches!(
+            operator,
+            "-" | "+"
+                | "*"
+                | "/"
+                | "%"
+                | "&"
+                | "|"
+                | "^"
+                | "<<"
+                | ">>"
+                | "pow"
+                | "min"
+                | "max"
+                | "<"
+                | "<="
+                | "=="
+                | "!="
+                | ">="
+                | ">"
+        )
+    }
+
+    fn apply_unary(&self, operator: &str, value: f64) -> Result<f64, String> {
+        let result: f64 = match operator {
+            "-" => -value,
+            "~" => !(value as i64) as f64,
+            "!" => bool_value(value == 0.0),
+            "sin" => value.sin(),
+            "cos" => value.cos(),
+            "tan" => value.tan(),
+            "exp" => value.exp(),
+            "log" => value.ln(),
+            "abs" => value.abs(),
+            "sqrt" => value.sqrt(),
+            "sign" => value.signum(),
+            "ceil" => value.ceil(),
+            "floor" => value.floor(),
+            _ => match self.context.byte_at(value as usize) {
+                Some(byte) => byte as f64,
+                None => return Err(format!("Cannot read address {} with '@'.", value)),
+            },
+        };
+        Ok(result)
+    }
+
+    fn apply_binary(&self, operator: &str, left: f64, right: f64) -> Result<f64, String> {
+        let result: f64 = match operator {
+            "-" => left - right,
+            "+" => left + right,
+            "*" => left * right,
+            "/" => left / right,
+            "%" => left % right,
+            "&" => ((left as i64) & (right as i64)) as f64,
+            "|" => ((left as i64) | (right as i64)) as f64,
+            "^" => ((left as i64) ^ (right as i64)) as f64,
+            "<<" | ">>" => return shift(operator, left, right),
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "<" => bool_value(left < right),
+            "<=" => bool_value(left <= right),
+            "==" => bool_value(left == right),
+            "!=" => bool_value(left != right),
+            ">=" => bool_value(left >= right),
+            _ => bool_value(left > right),
+        };
+        Ok(result)
+    }
+}
+
+// Shift counts outside 0-63 are an error rather than an overflow.
+fn shift(operator: &str, left: f64, right: f64) -> Result<f64, String> {
+    let shifted: Option<i64> = u32::try_from(right as i64)
+        .ok()
+        .and_then(|count| match operator {
+            "<<" => (left as i64).checked_shl(count),
+            _ => (left as i64).checked_shr(count),
+        });
+    shifted
+        .map(|value| value as f64)
+        .ok_or(format!("Cannot shift by {} with '{}'.", right, operator))
+}
+
+fn bool_value(b: bool) -> f64 {
+    if b {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+pub fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits): (bool, &str) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value: i64 = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()?
+    } else {
+        digits.parse::<i64>().ok()?
+    };
+    if negative {
+        Some(-value)
+    } else {
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::octo::tokenizer::Tokenizer;
+
+    struct Names;
+
+    impl CalcContext for Names {
+        fn lookup(&self, name: &str) -> Option<f64> {
+            match name {
+                "TEN" => Some(10.0),
+                _ => None,
+            }
+        }
+
+        fn byte_at(&self, address: usize) -> Option<u8> {
+            match address {
+                0x200 => Some(0xAB),
+                _ => None,
+            }
+        }
+    }
+
+    fn evaluate(expression: &str) -> Result<f64, String> {
+        let tokens: Vec<Token> = Tokenizer::new().tokenize(expression);
+        Calc::new(&tokens, &Names).evaluate()
+    }
+
+    #[test]
+    fn evaluates_right_to_left_without_precedence() {
+        assert_eq!(evaluate("1 + 2 * 3"), Ok(7.0));
+        assert_eq!(evaluate("2 * 3 + 1"), Ok(8.0));
+        assert_eq!(evaluate("( 2 * 3 ) + 1"), Ok(7.0));
+        assert_eq!(evaluate("10 - 4 - 3"), Ok(9.0));
+        assert_eq!(evaluate("TEN / 4"), Ok(2.5));
+    }
+
+    #[test]
+    fn applies_unary_operators() {
+        assert_eq!(evaluate("- 3 + 1"), Ok(-2.0));
+        assert_eq!(evaluate("! 0"), Ok(1.0));
+        assert_eq!(evaluate("~ 0"), Ok(-1.0));
+        assert_eq!(evaluate("@ 0x200"), Ok(171.0));
+        assert_eq!(evaluate("abs - 3"), Ok(3.0));
+        assert!(evaluate("@ 0x300").is_err());
+    }
+
+    #[test]
+    fn shifts_within_range() {
+        assert_eq!(evaluate("1 << 3"), Ok(8.0));
+        assert_eq!(evaluate("0x80 >> 7"), Ok(1.0));
+        assert_eq!(evaluate("1 << 63"), Ok(i64::MIN as f64));
+    }
+
+    #[test]
+    fn rejects_out_of_range_shifts() {
+        assert!(evaluate("1 << 64").is_err());
+        assert!(evaluate("1 >> 64").is_err());
+        assert!(evaluate("1 << -1").is_err());
+        assert!(evaluate("1 >> 1000000000000").is_err());
+    }
+
+    #[test]
+    fn reports_malformed_expressions() {
+        assert_eq!(
+            evaluate("1 +"),
+            Err("Unexpected end of expression.".to_string())
+        );
+        assert_eq!(
+            evaluate("( 1"),
+            Err("Expected ')' in expression.".to_string())
+        );
+        assert_eq!(
+            evaluate("1 foo 2"),
+            Err("Unknown binary operator 'foo'.".to_string())
+        );
+        assert_eq!(
+            evaluate("nope"),
+            Err("Undefined name 'nope' in expression.".to_string())
+        );
+    }
+}
@@ -0,0 +1,1028 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use crate::guestsystem::components::memory::PROGRAM_ADDRESS;
+
+use super::{
+    calc::{parse_number, Calc, CalcContext},
+    tokenizer::{Token, Tokenizer},
+};
+
+const MAX_ADDRESS: usize = 0xFFFF;
+const MAX_NNN: usize = 0x0FFF;
+const FLAG_REGISTER: u8 = 0xF;
+
+#[derive(Debug)]
+pub struct OctoError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for OctoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Octo error on line {}: {}", self.line, self.message)
+    }
+}
+
+// Maps the address of every compiled instruction to its line in the source.
+pub struct SourceMap {
+    lines: BTreeMap<u16, usize>,
+}
+
+impl SourceMap {
+    pub fn line_at(&self, address: u16) -> Option<usize> {
+        self.lines.get(&address).copied()
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = (u16, usize)> + '_ {
+        self.lines.iter().map(|(address, line)| (*address, *line))
+    }
+}
+
+enum FixupKind {
+    Nnn,
+    Long,
+    UnpackHigh(Option<u8>),
+    UnpackLow,
+}
+
+struct Fixup {
+    address: usize,
+    label: String,
+    kind: FixupKind,
+    line: usize,
+}
+
+#[derive(Clone)]
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+}
+
+enum Control {
+    Loop { start: usize, breaks: Vec<usize> },
+    If { jump: usize },
+    Else { jump: usize },
+}
+
+enum Operand {
+    Register(u8),
+    Byte(u8),
+    None,
+}
+
+struct Condition {
+    x: u8,
+    comparison: String,
+    operand: Operand,
+}
+
+pub struct OctoCompiler {
+    tokens: Vec<Token>,
+    rom: Vec<u8>,
+    written: Vec<bool>,
+    here: usize,
+    line: usize,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    control: Vec<Control>,
+    main_jump: bool,
+    source_lines: BTreeMap<u16, usize>,
+}
+
+impl OctoCompiler {
+    pub fn new(source: &str) -> OctoCompiler {
+        let mut tokens: Vec<Token> = Tokenizer::new().tokenize(source);
+        tokens.reverse();
+        OctoCompiler {
+            tokens,
+            rom: Vec::new(),
+            written: Vec::new(),
+            here: PROGRAM_ADDRESS,
+            line: 1,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            control: Vec::new(),
+            main_jump: false,
+            source_lines: BTreeMap::new(),
+        }
+    }
+
+    pub fn compile(mut self) -> Result<(Vec<u8>, SourceMap), OctoError> {
+        self.inst(0x1000)?;
+        self.main_jump = true;
+        while let Some(token) = self.next_token() {
+            self.statement(token)?;
+        }
+        if !self.control.is_empty() {
+            return Err(self.error("Unterminated 'loop' or 'if ... begin' block."));
+        }
+        if self.main_jump {
+            let main: usize = match self.labels.get("main") {
+                Some(address) => *address,
+                None => return Err(self.error("This program is missing a 'main' label.")),
+            };
+            self.patch_jump(PROGRAM_ADDRESS, main)?;
+        }
+        self.resolve_fixups()?;
+        let source_map: SourceMap = SourceMap {
+            lines: self.source_lines,
+        };
+        Ok((self.rom, source_map))
+    }
+
+    fn statement(&mut self, token: Token) -> Result<(), OctoError> {
+        match token.text.as_str() {
+            ":" => self.label(),
+            ":alias" => self.alias(),
+            ":const" => self.constant(),
+            ":calc" => self.calc_directive(),
+            ":byte" => self.byte_directive(),
+            ":org" => self.org(),
+            ":macro" => self.macro_definition(),
+            ":unpack" => self.unpack(),
+            ":next" => self.next_label(),
+            ":call" => self.address_inst(0x2000),
+            ":assert" => self.assert(),
+            ":breakpoint" => self.skip_tokens(1),
+            ":monitor" => self.skip_tokens(2),
+            "clear" => self.inst(0x00E0),
+            "return" | ";" => self.inst(0x00EE),
+            "scroll-down" => self.nibble_inst(0x00C0),
+            "scroll-up" => self.nibble_inst(0x00D0),
+            "scroll-right" => self.inst(0x00FB),
+            "scroll-left" => self.inst(0x00FC),
+            "exit" => self.inst(0x00FD),
+            "lores" => self.inst(0x00FE),
+            "hires" => self.inst(0x00FF),
+            "audio" => self.inst(0xF002),
+            "plane" => {
+                let n: u8 = self.expect_nibble()?;
+                self.inst(0xF001 | (n as u16) << 8)
+            }
+            "bcd" => self.x_inst(0xF033),
+            "saveflags" => self.x_inst(0xF075),
+            "loadflags" => self.x_inst(0xF085),
+            "save" => self.save_or_load(0xF055, 0x5002),
+            "load" => self.save_or_load(0xF065, 0x5003),
+            "sprite" => self.sprite(),
+            "jump" => self.address_inst(0x1000),
+            "jump0" => self.address_inst(0xB000),
+            "native" => self.address_inst(0x0000),
+            "delay" => self.timer_assignment(0xF015),
+            "buzzer" => self.timer_assignment(0xF018),
+            "pitch" => self.timer_assignment(0xF03A),
+            "i" => self.index_statement(),
+            "loop" => {
+                self.control.push(Control::Loop {
+                    start: self.here,
+                    breaks: Vec::new(),
+                });
+                Ok(())
+            }
+            "while" => self.while_statement(),
+            "again" => self.again(),
+            "if" => self.if_statement(),
+            "else" => self.else_statement(),
+            "end" => self.end_statement(),
+            text => self.other_statement(text),
+        }
+    }
+
+    fn other_statement(&mut self, text: &str) -> Result<(), OctoError> {
+        if text.starts_with(':') {
+            return Err(self.error(&format!("Unknown directive '{}'.", text)));
+        }
+        if let Some(x) = self.register(text) {
+            return self.register_statement(x);
+        }
+        if let Some(value) = self.value_of(text) {
+            let byte: u8 = self.checked_byte(value)?;
+            return self.emit_byte(byte);
+        }
+        if let Some(definition) = self.macros.get(text).cloned() {
+            return self.expand_macro(&definition);
+        }
+        self.address_from(0x2000, text)
+    }
+
+    fn label(&mut self) -> Result<(), OctoError> {
+        let name: String = self.expect_token()?.text;
+        if name == "main" && self.main_jump && self.here == PROGRAM_ADDRESS + 2 {
+            self.rom.clear();
+            self.written.clear();
+            self.source_lines.clear();
+            self.here = PROGRAM_ADDRESS;
+            self.main_jump = false;
+        }
+        self.define_label(name, self.here)
+    }
+
+    fn next_label(&mut self) -> Result<(), OctoError> {
+        let name: String = self.expect_token()?.text;
+        self.define_label(name, self.here + 1)
+    }
+
+    fn define_label(&mut self, name: String, address: usize) -> Result<(), OctoError> {
+        if parse_number(&name).is_some() || self.register(&name).is_some() {
+            return Err(self.error(&format!("'{}' is not a valid label name.", name)));
+        }
+        if self.labels.contains_key(&name) {
+            return Err(self.error(&format!("The label '{}' is already defined.", name)));
+        }
+        self.labels.insert(name, address);
+        Ok(())
+    }
+
+    fn alias(&mut self) -> Result<(), OctoError> {
+        let name: String = self.expect_token()?.text;
+        let register: u8 = self.expect_register()?;
+        self.aliases.insert(name, register);
+        Ok(())
+    }
+
+    fn constant(&mut self) -> Result<(), OctoError> {
+        let name: String = self.expect_token()?.text;
+        let token: Token = self.expect_token()?;
+        let value: f64 = match self.value_of(&token.text) {
+            Some(value) => value as f64,
+            None => match self.labels.get(&token.text) {
+                Some(address) => *address as f64,
+                None => return Err(self.error(&format!("Undefined constant '{}'.", token.text))),
+            },
+        };
+        self.constants.insert(name, value);
+        Ok(())
+    }
+
+    fn calc_directive(&mut self) -> Result<(), OctoError> {
+        let name: String = self.expect_token()?.text;
+        let value: f64 = self.braced_expression()?;
+        self.constants.insert(name, value);
+        Ok(())
+    }
+
+    fn byte_directive(&mut self) -> Result<(), OctoError> {
+        let token: Token = self.expect_token()?;
+        if token.text == "{" {
+            self.tokens.push(token);
+            let value: f64 = self.braced_expression()?;
+            return self.emit_byte((value.floor() as i64 & 0xFF) as u8);
+        }
+        let byte: u8 = self.byte_of(&token.text)?;
+        self.emit_byte(byte)
+    }
+
+    fn org(&mut self) -> Result<(), OctoError> {
+        let token: Token = self.expect_token()?;
+        let address: i64 = match self.value_of(&token.text) {
+            Some(address) => address,
+            None => return Err(self.error(&format!("Invalid address '{}'.", token.text))),
+        };
+        if address < PROGRAM_ADDRESS as i64 || address > MAX_ADDRESS as i64 {
+            return Err(self.error(&format!("Address 0x{:X} is out of range.", address)));
+        }
+        self.here = address as usize;
+        Ok(())
+    }
+
+    fn assert(&mut self) -> Result<(), OctoError> {
+        let mut message: String = String::from("Assertion failed.");
+        if let Some(token) = self.tokens.last() {
+            if token.text.starts_with('"') {
+                message = format!("Assertion failed: {}", token.text.trim_matches('"'));
+                self.next_token();
+            }
+        }
+        if self.braced_expression()? == 0.0 {
+            return Err(self.error(&message));
+        }
+        Ok(())
+    }
+
+    fn braced_expression(&mut self) -> Result<f64, OctoError> {
+        let open: Token = self.expect_token()?;
+        if open.text != "{" {
+            return Err(self.error("Expected '{' to start an expression."));
+        }
+        let mut expression: Vec<Token> = Vec::new();
+        loop {
+            let token: Token = self.expect_token()?;
+            if token.text == "}" {
+                break;
+            }
+            expression.push(token);
+        }
+        let result: Result<f64, String> = Calc::new(&expression, self).evaluate();
+        result.map_err(|message| self.error(&message))
+    }
+
+    fn macro_definition(&mut self) -> Result<(), OctoError> {
+        let name: String = self.expect_token()?.text;
+        let mut args: Vec<String> = Vec::new();
+        loop {
+            let token: Token = self.expect_token()?;
+            if token.text == "{" {
+                break;
+            }
+            args.push(token.text);
+        }
+        let mut body: Vec<Token> = Vec::new();
+        let mut depth: usize = 1;
+        loop {
+            let token: Token = self.expect_token()?;
+            if token.text == "{" {
+                depth += 1;
+            } else if token.text == "}" {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            body.push(token);
+        }
+        self.macros.insert(name, Macro { args, body });
+        Ok(())
+    }
+
+    fn expand_macro(&mut self, definition: &Macro) -> Result<(), OctoError> {
+        let mut bindings: HashMap<&str, Token> = HashMap::new();
+        for arg in definition.args.iter() {
+            bindings.insert(arg.as_str(), self.expect_token()?);
+        }
+        for token in definition.body.iter().rev() {
+            match bindings.get(token.text.as_str()) {
+                Some(bound) => self.tokens.push(bound.clone()),
+                None => self.tokens.push(token.clone()),
+            }
+        }
+        Ok(())
+    }
+
+    fn unpack(&mut self) -> Result<(), OctoError> {
+        let token: Token = self.expect_token()?;
+        let nibble: Option<u8> = if token.text == "long" {
+            None
+        } else {
+            Some(self.nibble_of(&token.text)?)
+        };
+        let label: String = self.expect_token()?.text;
+        let high_register: u8 = *self.aliases.get("unpack-hi").unwrap_or(&0x0);
+        let low_register: u8 = *self.aliases.get("unpack-lo").unwrap_or(&0x1);
+
+        self.inst(0x6000 | (high_register as u16) << 8)?;
+        self.add_fixup(self.here - 1, &label, FixupKind::UnpackHigh(nibble));
+        self.inst(0x6000 | (low_register as u16) << 8)?;
+        self.add_fixup(self.here - 1, &label, FixupKind::UnpackLow);
+        Ok(())
+    }
+
+    fn skip_tokens(&mut self, count: usize) -> Result<(), OctoError> {
+        for _ in 0..count {
+            self.expect_token()?;
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, x: u8) -> Result<(), OctoError> {
+        let operator: String = self.expect_token()?.text;
+        let rhs: Token = self.expect_token()?;
+        let xy = |y: u8, n: u16| 0x8000 | (x as u16) << 8 | (y as u16) << 4 | n;
+        let xnn = |opcode: u16, nn: u8| opcode | (x as u16) << 8 | nn as u16;
+
+        match (operator.as_str(), self.register(&rhs.text)) {
+            (":=", Some(y)) => self.inst(xy(y, 0x0)),
+            (":=", None) => match rhs.text.as_str() {
+                "random" => {
+                    let nn: u8 = self.expect_byte()?;
+                    self.inst(xnn(0xC000, nn))
+                }
+                "delay" => self.inst(xnn(0xF007, 0)),
+                "key" => self.inst(xnn(0xF00A, 0)),
+                text => {
+                    let nn: u8 = self.byte_of(text)?;
+                    self.inst(xnn(0x6000, nn))
+                }
+            },
+            ("+=", Some(y)) => self.inst(xy(y, 0x4)),
+            ("+=", None) => {
+                let nn: u8 = self.byte_of(&rhs.text)?;
+                self.inst(xnn(0x7000, nn))
+            }
+            ("-=", Some(y)) => self.inst(xy(y, 0x5)),
+            ("-=", None) => {
+                let nn: u8 = self.byte_of(&rhs.text)?;
+                self.inst(xnn(0x7000, nn.wrapping_neg()))
+            }
+            ("=-", Some(y)) => self.inst(xy(y, 0x7)),
+            ("|=", Some(y)) => self.inst(xy(y, 0x1)),
+            ("&=", Some(y)) => self.inst(xy(y, 0x2)),
+            ("^=", Some(y)) => self.inst(xy(y, 0x3)),
+            (">>=", Some(y)) => self.inst(xy(y, 0x6)),
+            ("<<=", Some(y)) => self.inst(xy(y, 0xE)),
+            (_, _) => Err(self.error(&format!(
+                "Unrecognized register operation '{} {}'.",
+                operator, rhs.text
+            ))),
+        }
+    }
+
+    fn index_statement(&mut self) -> Result<(), OctoError> {
+        let operator: String = self.expect_token()?.text;
+        match operator.as_str() {
+            ":=" => {
+                let rhs: Token = self.expect_token()?;
+                match rhs.text.as_str() {
+                    "hex" => self.x_inst(0xF029),
+                    "bighex" => self.x_inst(0xF030),
+                    "long" => self.long_index(),
+                    text => self.address_from(0xA000, text),
+                }
+            }
+            "+=" => self.x_inst(0xF01E),
+            _ => Err(self.error(&format!("Unrecognized index operation '{}'.", operator))),
+        }
+    }
+
+    fn long_index(&mut self) -> Result<(), OctoError> {
+        let token: Token = self.expect_token()?;
+        self.inst(0xF000)?;
+        if let Some(address) = self.known_address(&token.text) {
+            if address > MAX_ADDRESS {
+                return Err(self.error(&format!("Address 0x{:X} is out of range.", address)));
+            }
+            return self.inst(address as u16);
+        }
+        self.add_fixup(self.here, &token.text, FixupKind::Long);
+        self.inst(0x0000)
+    }
+
+    fn timer_assignment(&mut self, opcode: u16) -> Result<(), OctoError> {
+        let operator: String = self.expect_token()?.text;
+        if operator != ":=" {
+            return Err(self.error(&format!("Expected ':=' but found '{}'.", operator)));
+        }
+        self.x_inst(opcode)
+    }
+
+    fn save_or_load(&mut self, single_opcode: u16, range_opcode: u16) -> Result<(), OctoError> {
+        let x: u8 = self.expect_register()?;
+        let is_range: bool = matches!(self.tokens.last(), Some(token) if token.text == "-");
+        if is_range {
+            self.next_token();
+            let y: u8 = self.expect_register()?;
+            return self.inst(range_opcode | (x as u16) << 8 | (y as u16) << 4);
+        }
+        self.inst(single_opcode | (x as u16) << 8)
+    }
+
+    fn sprite(&mut self) -> Result<(), OctoError> {
+        let x: u8 = self.expect_register()?;
+        let y: u8 = self.expect_register()?;
+        let n: u8 = self.expect_nibble()?;
+        self.inst(0xD000 | (x as u16) << 8 | (y as u16) << 4 | n as u16)
+    }
+
+    fn while_statement(&mut self) -> Result<(), OctoError> {
+        let condition: Condition = self.condition()?;
+        self.emit_condition(&condition, true)?;
+        let jump: usize = self.here;
+        self.inst(0x1000)?;
+        let innermost_loop = self
+            .control
+            .iter_mut()
+            .rev()
+            .find_map(|control| match control {
+                Control::Loop { breaks, .. } => Some(breaks),
+                _ => None,
+            });
+        match innermost_loop {
+            Some(breaks) => {
+                breaks.push(jump);
+                Ok(())
+            }
+            None => Err(self.error("'while' must be inside a 'loop'.")),
+        }
+    }
+
+    fn again(&mut self) -> Result<(), OctoError> {
+        match self.control.pop() {
+            Some(Control::Loop { start, breaks }) => {
+                self.inst(0x1000)?;
+                self.patch_jump(self.here - 2, start)?;
+                for jump in breaks {
+                    self.patch_jump(jump, self.here)?;
+                }
+                Ok(())
+            }
+            _ => Err(self.error("'again' without a matching 'loop'.")),
+        }
+    }
+
+    fn if_statement(&mut self) -> Result<(), OctoError> {
+        let condition: Condition = self.condition()?;
+        let keyword: String = self.expect_token()?.text;
+        match keyword.as_str() {
+            "then" => {
+                self.emit_condition(&condition, false)?;
+                let body: Token = self.expect_token()?;
+                self.statement(body)
+            }
+            "begin" => {
+                self.emit_condition(&condition, true)?;
+                self.control.push(Control::If { jump: self.here });
+                self.inst(0x1000)
+            }
+            _ => Err(self.error(&format!(
+                "Expected 'then' or 'begin' but found '{}'.",
+                keyword
+            ))),
+        }
+    }
+
+    fn else_statement(&mut self) -> Result<(), OctoError> {
+        match self.control.pop() {
+            Some(Control::If { jump }) => {
+                let else_jump: usize = self.here;
+                self.inst(0x1000)?;
+                self.patch_jump(jump, self.here)?;
+                self.control.push(Control::Else { jump: else_jump });
+                Ok(())
+            }
+            _ => Err(self.error("'else' without a matching 'if ... begin'.")),
+        }
+    }
+
+    fn end_statement(&mut self) -> Result<(), OctoError> {
+        match self.control.pop() {
+            Some(Control::If { jump }) | Some(Control::Else { jump }) => {
+                self.patch_jump(jump, self.here)
+            }
+            _ => Err(self.error("'end' without a matching 'if ... begin'.")),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, OctoError> {
+        let x: u8 = self.expect_register()?;
+        let comparison: String = self.expect_token()?.text;
+        let operand: Operand = match comparison.as_str() {
+            "key" | "-key" => Operand::None,
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => {
+                let rhs: Token = self.expect_token()?;
+                match self.register(&rhs.text) {
+                    Some(y) => Operand::Register(y),
+                    None => Operand::Byte(self.byte_of(&rhs.text)?),
+                }
+            }
+            _ => return Err(self.error(&format!("Unknown comparison '{}'.", comparison))),
+        };
+        Ok(Condition {
+            x,
+            comparison,
+            operand,
+        })
+    }
+
+    // Emits a test that skips the following instruction unless the condition
+    // (or its negation) holds.
+    fn emit_condition(&mut self, condition: &Condition, negate: bool) -> Result<(), OctoError> {
+        let comparison: &str = if negate {
+            match condition.comparison.as_str() {
+                "==" => "!=",
+                "!=" => "==",
+                "<" => ">=",
+                ">" => "<=",
+                "<=" => ">",
+                ">=" => "<",
+                "key" => "-key",
+                _ => "key",
+            }
+        } else {
+            condition.comparison.as_str()
+        };
+        let x: u16 = (condition.x as u16) << 8;
+        match (comparison, &condition.operand) {
+            ("==", Operand::Register(y)) => self.inst(0x9000 | x | (*y as u16) << 4),
+            ("==", Operand::Byte(nn)) => self.inst(0x4000 | x | *nn as u16),
+            ("!=", Operand::Register(y)) => self.inst(0x5000 | x | (*y as u16) << 4),
+            ("!=", Operand::Byte(nn)) => self.inst(0x3000 | x | *nn as u16),
+            ("key", _) => self.inst(0xE0A1 | x),
+            ("-key", _) => self.inst(0xE09E | x),
+            (_, operand) => {
+                let flag: u16 = (FLAG_REGISTER as u16) << 8;
+                match operand {
+                    Operand::Register(y) => self.inst(0x8000 | flag | (*y as u16) << 4)?,
+                    Operand::Byte(nn) => self.inst(0x6000 | flag | *nn as u16)?,
+                    Operand::None => {}
+                }
+                match comparison {
+                    "<" | ">=" => self.inst(0x8007 | flag | x >> 4)?,
+                    _ => self.inst(0x8005 | flag | x >> 4)?,
+                }
+                match comparison {
+                    "<" | ">" => self.inst(0x3000 | flag | 1),
+                    _ => self.inst(0x4000 | flag | 1),
+                }
+            }
+        }
+    }
+
+    fn x_inst(&mut self, opcode: u16) -> Result<(), OctoError> {
+        let x: u8 = self.expect_register()?;
+        self.inst(opcode | (x as u16) << 8)
+    }
+
+    fn nibble_inst(&mut self, opcode: u16) -> Result<(), OctoError> {
+        let n: u8 = self.expect_nibble()?;
+        self.inst(opcode | n as u16)
+    }
+
+    fn address_inst(&mut self, opcode: u16) -> Result<(), OctoError> {
+        let token: Token = self.expect_token()?;
+        self.address_from(opcode, &token.text)
+    }
+
+    fn address_from(&mut self, opcode: u16, text: &str) -> Result<(), OctoError> {
+        if let Some(address) = self.known_address(text) {
+            if address > MAX_NNN {
+                return Err(
+                    self.error(&format!("Address 0x{:X} does not fit in 12 bits.", address))
+                );
+            }
+            return self.inst(opcode | address as u16);
+        }
+        if self.register(text).is_some() || text.starts_with(':') {
+            return Err(self.error(&format!("Expected an address but found '{}'.", text)));
+        }
+        self.add_fixup(self.here, text, FixupKind::Nnn);
+        self.inst(opcode)
+    }
+
+    fn known_address(&self, text: &str) -> Option<usize> {
+        match self.value_of(text) {
+            Some(value) if value >= 0 => Some(value as usize),
+            _ => self.labels.get(text).copied(),
+        }
+    }
+
+    fn add_fixup(&mut self, address: usize, label: &str, kind: FixupKind) {
+        self.fixups.push(Fixup {
+            address,
+            label: label.to_string(),
+            kind,
+            line: self.line,
+        });
+    }
+
+    fn resolve_fixups(&mut self) -> Result<(), OctoError> {
+        let fixups: Vec<Fixup> = std::mem::take(&mut self.fixups);
+        for fixup in fixups {
+            self.line = fixup.line;
+            let target: usize = match self.labels.get(&fixup.label) {
+                Some(target) => *target,
+                None => return Err(self.error(&format!("Undefined label '{}'.", fixup.label))),
+            };
+            match fixup.kind {
+                FixupKind::Nnn => {
+                    if target > MAX_NNN {
+                        return Err(self.error(&format!(
+                            "The label '{}' at 0x{:X} does not fit in 12 bits.",
+                            fixup.label, target
+                        )));
+                    }
+                    let high: u8 = self.byte_at_address(fixup.address) | (target >> 8) as u8;
+                    self.set_byte(fixup.address, high);
+                    self.set_byte(fixup.address + 1, (target & 0xFF) as u8);
+                }
+                FixupKind::Long => {
+                    self.set_byte(fixup.address, (target >> 8) as u8);
+                    self.set_byte(fixup.address + 1, (target & 0xFF) as u8);
+                }
+                FixupKind::UnpackHigh(Some(nibble)) => {
+                    self.set_byte(fixup.address, nibble << 4 | ((target >> 8) & 0x0F) as u8)
+                }
+                FixupKind::UnpackHigh(None) => self.set_byte(fixup.address, (target >> 8) as u8),
+                FixupKind::UnpackLow => self.set_byte(fixup.address, (target & 0xFF) as u8),
+            }
+        }
+        Ok(())
+    }
+
+    fn patch_jump(&mut self, address: usize, target: usize) -> Result<(), OctoError> {
+        if target > MAX_NNN {
+            return Err(self.error(&format!(
+                "Jump target 0x{:X} does not fit in 12 bits.",
+                target
+            )));
+        }
+        let high: u8 = (self.byte_at_address(address) & 0xF0) | (target >> 8) as u8;
+        self.set_byte(address, high);
+        self.set_byte(address + 1, (target & 0xFF) as u8);
+        Ok(())
+    }
+
+    fn inst(&mut self, word: u16) -> Result<(), OctoError> {
+        self.source_lines.insert(self.here as u16, self.line);
+        self.emit_byte((word >> 8) as u8)?;
+        self.emit_byte((word & 0xFF) as u8)
+    }
+
+    fn emit_byte(&mut self, byte: u8) -> Result<(), OctoError> {
+        if self.here > MAX_ADDRESS {
+            return Err(self.error("The program is too large."));
+        }
+        let index: usize = self.here - PROGRAM_ADDRESS;
+        if index < self.written.len() && self.written[index] {
+            return Err(self.error(&format!("Data overlap at address 0x{:X}.", self.here)));
+        }
+        self.set_byte(self.here, byte);
+        self.written[index] = true;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn set_byte(&mut self, address: usize, byte: u8) {
+        let index: usize = address - PROGRAM_ADDRESS;
+        if index >= self.rom.len() {
+            self.rom.resize(index + 1, 0);
+            self.written.resize(index + 1, false);
+        }
+        self.rom[index] = byte;
+    }
+
+    fn byte_at_address(&self, address: usize) -> u8 {
+        self.rom[address - PROGRAM_ADDRESS]
+    }
+
+    fn register(&self, text: &str) -> Option<u8> {
+        if let Some(register) = self.aliases.get(text) {
+            return Some(*register);
+        }
+        let lower: String = text.to_lowercase();
+        let digit: &str = lower.strip_prefix('v')?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u8::from_str_radix(digit, 16).ok()
+    }
+
+    fn value_of(&self, text: &str) -> Option<i64> {
+        match parse_number(text) {
+            Some(value) => Some(value),
+            None => self.constants.get(text).map(|value| value.floor() as i64),
+        }
+    }
+
+    fn byte_of(&self, text: &str) -> Result<u8, OctoError> {
+        match self.value_of(text) {
+            Some(value) => self.checked_byte(value),
+            None => Err(self.error(&format!("Expected a byte value but found '{}'.", text))),
+        }
+    }
+
+    fn nibble_of(&self, text: &str) -> Result<u8, OctoError> {
+        match self.value_of(text) {
+            Some(value) if (0..=0xF).contains(&value) => Ok(value as u8),
+            _ => Err(self.error(&format!(
+                "Expected a value from 0 to 15 but found '{}'.",
+                text
+            ))),
+        }
+    }
+
+    fn checked_byte(&self, value: i64) -> Result<u8, OctoError> {
+        if !(-128..=255).contains(&value) {
+            return Err(self.error(&format!("The value {} does not fit in a byte.", value)));
+        }
+        Ok((value & 0xFF) as u8)
+    }
+
+    fn expect_register(&mut self) -> Result<u8, OctoError> {
+        let token: Token = self.expect_token()?;
+        match self.register(&token.text) {
+            Some(register) => Ok(register),
+            None => Err(self.error(&format!("Expected a register but found '{}'.", token.text))),
+        }
+    }
+
+    fn expect_byte(&mut self) -> Result<u8, OctoError> {
+        let token: Token = self.expect_token()?;
+        self.byte_of(&token.text)
+    }
+
+    fn expect_nibble(&mut self) -> Result<u8, OctoError> {
+        let token: Token = self.expect_token()?;
+        self.nibble_of(&token.text)
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        let token: Option<Token> = self.tokens.pop();
+        if let Some(t) = &token {
+            self.line = t.line;
+        }
+        token
+    }
+
+    fn expect_token(&mut self) -> Result<Token, OctoError> {
+        match self.next_token() {
+            Some(token) => Ok(token),
+            None => Err(self.error("Unexpected end of file.")),
+        }
+    }
+
+    fn error(&self, message: &str) -> OctoError {
+        OctoError {
+            line: self.line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl CalcContext for OctoCompiler {
+    fn lookup(&self, name: &str) -> Option<f64> {
+        if name == "HERE" {
+            return Some(self.here as f64);
+        }
+        match self.constants.get(name) {
+            Some(value) => Some(*value),
+            None => self.labels.get(name).map(|address| *address as f64),
+        }
+    }
+
+    fn byte_at(&self, address: usize) -> Option<u8> {
+        let index: usize = address.checked_sub(PROGRAM_ADDRESS)?;
+        self.rom.get(index).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(source: &str) -> Result<Vec<u16>, OctoError> {
+        let (rom, _): (Vec<u8>, SourceMap) = OctoCompiler::new(source).compile()?;
+        Ok(rom
+            .chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+            .collect())
+    }
+
+    fn compile_main(body: &str) -> Vec<u16> {
+        compile(&format!(": main\n{}", body)).unwrap()
+    }
+
+    fn error_line(source: &str) -> usize {
+        match OctoCompiler::new(source).compile() {
+            Ok(_) => panic!("'{}' compiled without an error", source),
+            Err(err) => err.line,
+        }
+    }
+
+    #[test]
+    fn encodes_every_statement_form() {
+        let cases: [(&str, &[u16]); 54] = [
+            ("clear", &[0x00E0]),
+            ("return", &[0x00EE]),
+            (";", &[0x00EE]),
+            ("scroll-down 3", &[0x00C3]),
+            ("scroll-up 2", &[0x00D2]),
+            ("scroll-right", &[0x00FB]),
+            ("scroll-left", &[0x00FC]),
+            ("exit", &[0x00FD]),
+            ("lores", &[0x00FE]),
+            ("hires", &[0x00FF]),
+            ("audio", &[0xF002]),
+            ("plane 3", &[0xF301]),
+            ("bcd v1", &[0xF133]),
+            ("saveflags v2", &[0xF275]),
+            ("loadflags v3", &[0xF385]),
+            ("save v4", &[0xF455]),
+            ("load v5", &[0xF565]),
+            ("save v1 - v3", &[0x5132]),
+            ("load v2 - v4", &[0x5243]),
+            ("sprite v1 v2 5", &[0xD125]),
+            ("jump 0x300", &[0x1300]),
+            ("jump0 0x300", &[0xB300]),
+            ("native 0x300", &[0x0300]),
+            (":call 0x300", &[0x2300]),
+            ("delay := v1", &[0xF115]),
+            ("buzzer := v2", &[0xF218]),
+            ("pitch := v3", &[0xF33A]),
+            ("i := 0x345", &[0xA345]),
+            ("i := hex v1", &[0xF129]),
+            ("i := bighex v2", &[0xF230]),
+            ("i := long 0x1234", &[0xF000, 0x1234]),
+            ("i += v3", &[0xF31E]),
+            ("v1 := v2", &[0x8120]),
+            ("v1 := 0x42", &[0x6142]),
+            ("v1 := random 0x0F", &[0xC10F]),
+            ("v1 := delay", &[0xF107]),
+            ("v1 := key", &[0xF10A]),
+            ("v1 += v2", &[0x8124]),
+            ("v1 += 5", &[0x7105]),
+            ("v1 -= v2", &[0x8125]),
+            ("v1 -= 1", &[0x71FF]),
+            ("v1 =- v2", &[0x8127]),
+            ("v1 |= v2", &[0x8121]),
+            ("v1 &= v2", &[0x8122]),
+            ("v1 ^= v2", &[0x8123]),
+            ("v1 >>= v2", &[0x8126]),
+            ("v1 <<= v2", &[0x812E]),
+            ("if v1 == 5 then clear", &[0x4105, 0x00E0]),
+            ("if v1 != v2 then clear", &[0x5120, 0x00E0]),
+            ("if v1 key then clear", &[0xE1A1, 0x00E0]),
+            ("if v1 -key then clear", &[0xE19E, 0x00E0]),
+            ("if v1 < 5 then clear", &[0x6F05, 0x8F17, 0x3F01, 0x00E0]),
+            ("if v1 >= v2 then clear", &[0x8F20, 0x8F17, 0x4F01, 0x00E0]),
+            ("0x12 0x34 :byte 0x56 :byte { 0x50 + 8 }", &[0x1234, 0x5658]),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(compile_main(source), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn jumps_to_main_when_it_is_not_first() {
+        assert_eq!(
+            compile("clear\n: main\nreturn").unwrap(),
+            [0x1204, 0x00E0, 0x00EE]
+        );
+    }
+
+    #[test]
+    fn resolves_labels_aliases_and_unpack() {
+        assert_eq!(
+            compile_main("sub\n:alias x v3\nx := 1\n: sub\nreturn"),
+            [0x2204, 0x6301, 0x00EE]
+        );
+        assert_eq!(
+            compile_main(":unpack 0xA data\n: data\n0x12 0x34"),
+            [0x60A2, 0x6104, 0x1234]
+        );
+    }
+
+    #[test]
+    fn patches_if_begin_else_end() {
+        assert_eq!(
+            compile_main("if v0 == 1 begin\nclear\nend"),
+            [0x3001, 0x1206, 0x00E0]
+        );
+        assert_eq!(
+            compile_main("if v0 == 1 begin\nv1 := 1\nelse\nv1 := 2\nend"),
+            [0x3001, 0x1208, 0x6101, 0x120A, 0x6102]
+        );
+    }
+
+    #[test]
+    fn patches_loop_while_again() {
+        assert_eq!(
+            compile_main("loop\nwhile v0 != 3\nv0 += 1\nagain"),
+            [0x4003, 0x1208, 0x7001, 0x1200]
+        );
+        assert_eq!(
+            compile_main("loop\nloop\nwhile v0 key\nagain\nwhile v1 key\nagain"),
+            [0xE09E, 0x1206, 0x1200, 0xE19E, 0x120C, 0x1200]
+        );
+    }
+
+    #[test]
+    fn evaluates_calc_right_to_left() {
+        assert_eq!(
+            compile_main(":calc a { 1 + 2 * 3 }\n:calc b { 2 * 3 + 1 }\nv0 := a\nv1 := b"),
+            [0x6007, 0x6108]
+        );
+        assert_eq!(
+            compile_main(":const c 4\n:calc d { ( c * 2 ) + 1 }\nv0 := d"),
+            [0x6009]
+        );
+    }
+
+    #[test]
+    fn reports_the_line_of_an_error() {
+        assert_eq!(error_line(": main\nclear\nv0 := 300"), 3);
+        assert_eq!(error_line(": main\nclear\n\nfoo bar"), 4);
+        assert_eq!(error_line(": main\njump nowhere\nclear\nclear"), 2);
+        assert_eq!(error_line(": main\nclear\nelse"), 3);
+        assert_eq!(error_line(": main\n:calc x { 1 << 64 }\nclear"), 2);
+        assert_eq!(error_line(": main\nloop\nclear"), 3);
+        assert_eq!(error_line("clear\nclear"), 2);
+    }
+
+    #[test]
+    fn maps_instructions_to_source_lines() {
+        let (_, source_map): (Vec<u8>, SourceMap) = OctoCompiler::new(": main\nclear\n\nv0 := 1")
+            .compile()
+            .unwrap();
+        assert_eq!(source_map.line_at(0x200), Some(2));
+        assert_eq!(source_map.line_at(0x202), Some(4));
+        assert_eq!(source_map.line_at(0x204), None);
+    }
+}
@@ -0,0 +1,68 @@
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub text: String,
+    pub line: usize,
+}
+
+impl Token {
+    pub fn new(text: &str, line: usize) -> Token {
+        Token {
+            text: text.to_string(),
+            line,
+        }
+    }
+}
+
+pub struct Tokenizer;
+
+impl Tokenizer {
+    pub fn new() -> Tokenizer {
+        Tokenizer
+    }
+
+    pub fn tokenize(&self, source: &str) -> Vec<Token> {
+        let mut tokens: Vec<Token> = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            self.tokenize_line(line, index + 1, &mut tokens);
+        }
+        tokens
+    }
+
+    fn tokenize_line(&self, line: &str, line_number: usize, tokens: &mut Vec<Token>) {
+        let mut current: String = String::new();
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '"' && current.is_empty() {
+                let mut string: String = String::from("\"");
+                for s in chars.by_ref() {
+                    string.push(s);
+                    if s == '"' {
+                        break;
+                    }
+                }
+                tokens.push(Token::new(&string, line_number));
+            } else if c == '#' && current.is_empty() {
+                return;
+            } else if c.is_whitespace() {
+                self.flush(&mut current, line_number, tokens);
+            } else if self.is_bracket(c) {
+                self.flush(&mut current, line_number, tokens);
+                tokens.push(Token::new(&c.to_string(), line_number));
+            } else {
+                current.push(c);
+            }
+        }
+        self.flush(&mut current, line_number, tokens);
+    }
+
+    fn flush(&self, current: &mut String, line_number: usize, tokens: &mut Vec<Token>) {
+        if !current.is_empty() {
+            tokens.push(Token::new(current, line_number));
+            current.clear();
+        }
+    }
+
+    fn is_bracket(&self, c: char) -> bool {
+        matches!(c, '{' | '}' | '(' | ')')
+    }
+}
@@ -0,0 +1,170 @@
+use std::{collections::HashMap, fs::File, io::Write};
+
+use crate::guestsystem::components::{cpu::CpuInst, memory::Memory};
+
+use super::interpreter::Interpreter;
+
+const HOT_SPOT_COUNT: usize = 20;
+const MAIN_FRAME: &str = "main";
+
+pub struct Profiler {
+    total: u64,
+    pc_counts: HashMap<u16, u64>,
+    mnemonics: HashMap<u16, String>,
+    instruction_counts: HashMap<String, u64>,
+    call_stack: Vec<u16>,
+    self_counts: HashMap<Option<u16>, u64>,
+    inclusive_counts: HashMap<Option<u16>, u64>,
+    folded_counts: HashMap<String, u64>,
+    folded_path: Option<String>,
+}
+
+impl Profiler {
+    pub fn new(folded_path: Option<String>) -> Profiler {
+        Profiler {
+            total: 0,
+            pc_counts: HashMap::new(),
+            mnemonics: HashMap::new(),
+            instruction_counts: HashMap::new(),
+            call_stack: Vec::new(),
+            self_counts: HashMap::new(),
+            inclusive_counts: HashMap::new(),
+            folded_counts: HashMap::new(),
+            folded_path,
+        }
+    }
+
+    // Called before the instruction is executed, so the cycle is attributed
+    // to the subroutine that contains it.
+    pub fn record(
+        &mut self,
+        pc: u16,
+        instruction: &CpuInst,
+        memory: &Memory,
+        interpreter: &Interpreter,
+    ) {
+        self.call_stack.truncate(memory.get_stack_pointer());
+        self.total += 1;
+        *self.pc_counts.entry(pc).or_insert(0) += 1;
+        self.mnemonics
+            .entry(pc)
+            .or_insert_with(|| interpreter.mnemonic(instruction));
+        *self
+            .instruction_counts
+            .entry(self.variant_name(instruction))
+            .or_insert(0) += 1;
+
+        let current: Option<u16> = self.call_stack.last().copied();
+        *self.self_counts.entry(current).or_insert(0) += 1;
+        *self.inclusive_counts.entry(None).or_insert(0) += 1;
+        let mut seen: Vec<u16> = Vec::new();
+        for address in self.call_stack.iter() {
+            if !seen.contains(address) {
+                *self.inclusive_counts.entry(Some(*address)).or_insert(0) += 1;
+                seen.push(*address);
+            }
+        }
+        *self.folded_counts.entry(self.folded_stack()).or_insert(0) += 1;
+
+        match instruction {
+            CpuInst::SubRoutineNNN(nnn) => self.call_stack.push(*nnn),
+            CpuInst::SubRoutineReturn => {
+                self.call_stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn finish(&self) {
+        self.print_report();
+        if let Some(path) = &self.folded_path {
+            if let Err(err) = self.write_folded(path) {
+                println!("Cannot write folded stacks to {}: {}", path, err);
+            }
+        }
+    }
+
+    fn print_report(&self) {
+        println!("-- Profile: {} instructions executed --", self.total);
+        println!();
+        println!("  Hot spots:");
+        println!(
+            "  {:<8}{:>12}{:>9}  INSTRUCTION",
+            "ADDRESS", "COUNT", "SHARE"
+        );
+        for (pc, count) in self
+            .sorted(&self.pc_counts)
+            .into_iter()
+            .take(HOT_SPOT_COUNT)
+        {
+            println!(
+                "  0x{:04X}  {:>12}{:>8.2}%  {}",
+                pc,
+                count,
+                self.share(count),
+                self.mnemonics[&pc]
+            );
+        }
+        println!();
+        println!("  Instructions:");
+        for (name, count) in self.sorted(&self.instruction_counts) {
+            println!("  {:<20}{:>12}{:>8.2}%", name, count, self.share(count));
+        }
+        println!();
+        println!("  Subroutines:");
+        println!("  {:<8}{:>12}{:>12}", "ENTRY", "SELF", "INCLUSIVE");
+        for (entry, inclusive) in self.sorted(&self.inclusive_counts) {
+            let self_count: u64 = *self.self_counts.get(&entry).unwrap_or(&0);
+            println!(
+                "  {:<8}{:>12}{:>12}",
+                self.frame_name(entry),
+                self_count,
+                inclusive
+            );
+        }
+    }
+
+    fn write_folded(&self, path: &str) -> std::io::Result<()> {
+        let mut file: File = File::create(path)?;
+        for (stack, count) in self.sorted(&self.folded_counts) {
+            writeln!(file, "{} {}", stack, count)?;
+        }
+        Ok(())
+    }
+
+    fn folded_stack(&self) -> String {
+        let mut frames: Vec<String> = vec![MAIN_FRAME.to_string()];
+        for address in self.call_stack.iter() {
+            frames.push(self.frame_name(Some(*address)));
+        }
+        frames.join(";")
+    }
+
+    fn frame_name(&self, entry: Option<u16>) -> String {
+        match entry {
+            Some(address) => format!("0x{:04X}", address),
+            None => MAIN_FRAME.to_string(),
+        }
+    }
+
+    fn variant_name(&self, instruction: &CpuInst) -> String {
+        let debug: String = format!("{:?}", instruction);
+        match debug.split_once('(') {
+            Some((name, _)) => name.to_string(),
+            None => debug,
+        }
+    }
+
+    fn sorted<K: Clone + Ord>(&self, counts: &HashMap<K, u64>) -> Vec<(K, u64)> {
+        let mut entries: Vec<(K, u64)> = counts.iter().map(|(k, v)| (k.clone(), *v)).collect();
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        entries
+    }
+
+    fn share(&self, count: u64) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        count as f64 * 100.0 / self.total as f64
+    }
+}
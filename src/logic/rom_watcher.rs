@@ -0,0 +1,50 @@
+use std::{fs, time::SystemTime};
+
+// The file is checked every this many frames.
+const CHECK_INTERVAL: u32 = 15;
+
+// Notices when the ROM file is written to, so it can be reloaded.
+pub struct RomWatcher {
+    path: String,
+    modified: Option<SystemTime>,
+    countdown: u32,
+}
+
+impl RomWatcher {
+    pub fn new(path: &str) -> RomWatcher {
+        RomWatcher {
+            path: path.to_string(),
+            modified: modified_time(path),
+            countdown: CHECK_INTERVAL,
+        }
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn set_path(&mut self, path: &str) {
+        *self = RomWatcher::new(path);
+    }
+
+    // Called once per frame, true when the file changed since the last check.
+    pub fn poll(&mut self) -> bool {
+        self.countdown -= 1;
+        if self.countdown > 0 {
+            return false;
+        }
+        self.countdown = CHECK_INTERVAL;
+        let modified: Option<SystemTime> = modified_time(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
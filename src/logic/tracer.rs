@@ -0,0 +1,70 @@
+use std::io::Write;
+
+use crate::guestsystem::components::{cpu::Cpu, memory::Memory};
+
+// One line per executed instruction, e.g.
+// 00000001 PC:0200 OP:00E0 CLS              V0:00 ... VF:00 I:0000 SP:00 DT:00 ST:00
+// The column layout must stay fixed so traces can be diffed between runs and emulators.
+pub struct Tracer {
+    output: Box<dyn Write>,
+    pc_range: Option<(u16, u16)>,
+    cycle_range: Option<(u64, u64)>,
+    cycle: u64,
+    failed: bool,
+}
+
+impl Tracer {
+    pub fn new(
+        output: Box<dyn Write>,
+        pc_range: Option<(u16, u16)>,
+        cycle_range: Option<(u64, u64)>,
+    ) -> Tracer {
+        Tracer {
+            output,
+            pc_range,
+            cycle_range,
+            cycle: 0,
+            failed: false,
+        }
+    }
+
+    pub fn trace(&mut self, pc: u16, opcode: u16, mnemonic: &str, cpu: &Cpu, memory: &Memory) {
+        self.cycle += 1;
+        if self.failed || !self.in_range(pc) {
+            return;
+        }
+        let mut line: String = format!(
+            "{:08} PC:{:04X} OP:{:04X} {:<16}",
+            self.cycle, pc, opcode, mnemonic
+        );
+        for (i, v) in cpu.get_variable_registers().iter().enumerate() {
+            line.push_str(&format!(" V{:X}:{:02X}", i, v));
+        }
+        line.push_str(&format!(
+            " I:{:04X} SP:{:02X} DT:{:02X} ST:{:02X}",
+            cpu.get_index_register(),
+            memory.get_stack_pointer(),
+            cpu.get_delay_timer(),
+            cpu.get_sound_timer()
+        ));
+        // A closed pipe or a full disk stops the trace, not the emulator.
+        if let Err(err) = writeln!(self.output, "{}", line) {
+            eprintln!("Error while writing trace, tracing stopped: {}", err);
+            self.failed = true;
+        }
+    }
+
+    fn in_range(&self, pc: u16) -> bool {
+        if let Some((from, to)) = self.pc_range {
+            if pc < from || pc > to {
+                return false;
+            }
+        }
+        if let Some((from, to)) = self.cycle_range {
+            if self.cycle < from || self.cycle > to {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -1,7 +1,47 @@
 pub mod config;
+pub mod error;
 mod logic {
     pub mod args_service;
+    pub mod conformance;
+    pub mod coverage;
+    pub mod debugger;
+    pub mod game_db;
     pub mod interpreter;
+    pub mod movie;
+    pub mod profiler;
+    pub mod rom_watcher;
+    pub mod tracer;
+    pub mod octo {
+        pub mod calc;
+        pub mod compiler;
+        pub mod tokenizer;
+    }
+}
+mod host {
+    #[cfg(feature = "sdl")]
+    pub mod crt;
+    pub mod filter;
+    pub mod frame;
+    pub mod frontend;
+    pub mod headless_frontend;
+    #[cfg(feature = "sdl")]
+    pub mod memory_viewer;
+    #[cfg(feature = "sdl")]
+    pub mod menu;
+    pub mod palette;
+    pub mod recorder;
+    #[cfg(feature = "sdl")]
+    pub mod register_overlay;
+    pub mod screenshot;
+    #[cfg(feature = "sdl")]
+    pub mod sdl_frontend;
+    pub mod settings;
+    pub mod terminal_frontend;
+    #[cfg(feature = "sdl")]
+    pub mod text;
+    pub mod wav;
+    #[cfg(feature = "sdl")]
+    pub mod window;
 }
 mod guestsystem {
     pub mod components {
@@ -9,37 +49,194 @@ mod guestsystem {
         pub mod display;
         pub mod keypad;
         pub mod memory;
+        pub mod random;
     }
     pub mod guest_system;
+    pub mod save_state;
+    pub mod speed;
 }
 
 use config::CpuConfig;
 use guestsystem::{
-    components::{cpu::Cpu, memory::Memory},
+    components::{cpu::Cpu, memory::Memory, random::RandomSource},
     guest_system::GuestSystem,
 };
-use logic::{args_service::ArgsService, interpreter::Interpreter};
-use sdl2::Sdl;
-use std::env;
+use host::{frontend::Frontend, palette::Palette, settings::Settings};
+use logic::{
+    args_service::ArgsService,
+    conformance::{self, DEFAULT_MANIFEST_PATH},
+    game_db::{find_game, GameInfo},
+    interpreter::Interpreter,
+    movie::Movie,
+};
+use std::{env, process};
 
+#[cfg(feature = "sdl")]
 extern crate sdl2;
 
 pub fn main() {
     let args: Vec<String> = env::args().collect();
     let args_service: ArgsService = ArgsService::new();
+    if args.get(1).map(String::as_str) == Some("test-rom") {
+        let manifest_path: &str = args
+            .iter()
+            .skip(2)
+            .find(|arg| !arg.starts_with("--"))
+            .map_or(DEFAULT_MANIFEST_PATH, String::as_str);
+        let allow_missing: bool = args.iter().any(|arg| arg == "--allow-missing");
+        match conformance::run_manifest(manifest_path, allow_missing) {
+            Ok(true) => return,
+            Ok(false) => process::exit(1),
+            Err(msg) => {
+                println!("{}", msg);
+                process::exit(2);
+            }
+        }
+    }
     let path: String = args_service.find_path_arg(&args);
-    let mut cpu_config: CpuConfig = CpuConfig::default();
+    let mut cpu_config: CpuConfig = match args_service.find_quirk_profile(&args) {
+        Ok(Some(cpu_config)) => cpu_config,
+        Ok(None) => CpuConfig::default(),
+        Err(msg) => {
+            println!("{}", msg);
+            return;
+        }
+    };
     if args_service.find_config_arg(&args) {
         cpu_config = args_service.prompt_config();
     }
+    if let Err(msg) = args_service.apply_stack_args(&args, &mut cpu_config) {
+        println!("{}", msg);
+        return;
+    }
+    args_service.apply_quirk_args(&args, &mut cpu_config);
+    if let Err(msg) = args_service.apply_opcode_args(&args, &mut cpu_config) {
+        println!("{}", msg);
+        return;
+    }
+    let (rom_bytes, source_map) = match args_service.read_rom(&path) {
+        Ok(rom) => rom,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+    let game: Option<&GameInfo> = find_game(&rom_bytes);
+    let mut settings: Settings = match args_service.find_settings(&args) {
+        Ok(settings) => settings,
+        Err(msg) => {
+            println!("{}", msg);
+            return;
+        }
+    };
+    let palette: Palette = match args_service.find_palette(&args, &settings, game) {
+        Ok(palette) => palette,
+        Err(msg) => {
+            println!("{}", msg);
+            return;
+        }
+    };
+
+    let mut random: RandomSource = match args_service.find_random(&args) {
+        Ok(random) => random,
+        Err(msg) => {
+            println!("{}", msg);
+            return;
+        }
+    };
+    let movie: Option<Movie> =
+        match args_service.find_movie(&args, &rom_bytes, &random, &cpu_config) {
+            Ok(movie) => movie,
+            Err(msg) => {
+                println!("{}", msg);
+                return;
+            }
+        };
+    if let Some(movie) = &movie {
+        random = RandomSource::new(movie.get_random_kind(), movie.get_seed());
+        movie.apply_config(&mut cpu_config);
+    }
+
+    let memory: Memory = Memory::with_stack_depth(cpu_config.stack_depth());
+    let mut cpu: Cpu = Cpu::new(cpu_config);
+    match args_service.find_tracer(&args) {
+        Ok(Some(tracer)) => cpu.set_tracer(tracer),
+        Ok(None) => {}
+        Err(msg) => {
+            println!("{}", msg);
+            return;
+        }
+    }
+    cpu.set_random(random);
 
-    let sdl_context: Sdl = sdl2::init().unwrap();
-    let mut guest_system: GuestSystem =
-        GuestSystem::new(Memory::new(), Cpu::new(cpu_config), &sdl_context);
+    let mut guest_system: GuestSystem = GuestSystem::new(memory, cpu);
+    guest_system.set_rom_path(&path);
+    if let Some(movie) = movie {
+        guest_system.set_movie(movie);
+    }
+    match args_service.find_speed(&args) {
+        Ok(speed) => guest_system.set_speed(speed),
+        Err(msg) => {
+            println!("{}", msg);
+            return;
+        }
+    }
+    if let Some(watcher) = args_service.find_watcher(&args, &path) {
+        guest_system.set_watcher(watcher);
+    }
+    if let Some(profiler) = args_service.find_profiler(&args) {
+        guest_system.set_profiler(profiler);
+    }
+    if let Some(mut coverage) = args_service.find_coverage(&args) {
+        if let Some(source_map) = source_map {
+            coverage.set_source_map(&path, source_map);
+        }
+        guest_system.set_coverage(coverage);
+    }
+    match args_service.find_screenshot(&args, palette) {
+        Ok(screenshot) => guest_system.set_screenshot(screenshot),
+        Err(msg) => {
+            println!("{}", msg);
+            return;
+        }
+    }
+    match args_service.find_filter(&args, &settings) {
+        Ok(filter) => guest_system.set_filter(filter),
+        Err(msg) => {
+            println!("{}", msg);
+            return;
+        }
+    }
+    match args_service.find_recorder(&args, palette) {
+        Ok(recorder) => guest_system.set_recorder(recorder),
+        Err(msg) => {
+            println!("{}", msg);
+            return;
+        }
+    }
     let interpreter: Interpreter = Interpreter::new();
 
-    match args_service.read_rom(&path) {
-        Ok(rom_bytes) => guest_system.run_program(&rom_bytes, &interpreter),
-        Err(msg) => println!("{}", msg),
+    let title: Option<&str> = game.map(|game| game.title);
+    let mut frontend: Box<dyn Frontend> =
+        match args_service.find_frontend(&args, palette, title, &settings) {
+            Ok(frontend) => frontend,
+            Err(msg) => {
+                println!("{}", msg);
+                return;
+            }
+        };
+    let result = guest_system.run_program(&rom_bytes, &interpreter, frontend.as_mut());
+    let window_size: Option<(u32, u32)> = frontend.window_size();
+    drop(frontend);
+    if let Some((width, height)) = window_size.filter(|_| settings.is_persistent()) {
+        settings.set("window_width", width.to_string());
+        settings.set("window_height", height.to_string());
+        if let Err(msg) = settings.save() {
+            println!("{}", msg);
+        }
+    }
+    guest_system.finish(&interpreter);
+    if let Err(err) = result {
+        println!("{}", err);
     }
 }
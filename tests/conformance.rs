@@ -0,0 +1,33 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+#[test]
+fn test_roms_match_golden_framebuffers() {
+    let output: Output = Command::new(env!("CARGO_BIN_EXE_chip-8"))
+        .args(["test-rom", "roms/tests/conformance.txt"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("Cannot run the emulator.");
+    let report: String = String::from_utf8_lossy(&output.stdout).to_string();
+    assert!(output.status.success(), "{}", report);
+    assert!(report.contains("PASS  flags.8o [vip]"), "{}", report);
+}
+
+#[test]
+fn missing_roms_fail_unless_allowed() {
+    let manifest: PathBuf = env::temp_dir().join("rchip8-missing-manifest.txt");
+    fs::write(&manifest, "missing.ch8 modern 10 -\n").expect("Cannot write the manifest.");
+    let run = |extra: &[&str]| -> Output {
+        Command::new(env!("CARGO_BIN_EXE_chip-8"))
+            .arg("test-rom")
+            .arg(&manifest)
+            .args(extra)
+            .output()
+            .expect("Cannot run the emulator.")
+    };
+    assert_eq!(run(&[]).status.code(), Some(1));
+    assert!(run(&["--allow-missing"]).status.success());
+}